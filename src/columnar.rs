@@ -0,0 +1,357 @@
+//! Módulo columnar para parseit-rs.
+//! Arma el esquema Arrow de un formato y convierte los registros tipados en lotes
+//! (`RecordBatch`) para las salidas Parquet y Arrow IPC.
+//!
+//! Los tipos de las columnas se derivan de la definición de cada campo:
+//! - `zamount`, `amount`: `Decimal128` con la escala de `param1`.
+//! - `date`: `Date32`.
+//! - `table`: texto codificado como diccionario (`Dictionary<Int32, Utf8>`).
+//! - `string` (y cualquier otro): `Utf8`.
+//!
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::Arc;
+use arrow::array::{ArrayRef, Date32Builder, Decimal128Builder, StringBuilder, StringDictionaryBuilder};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDate;
+use crate::config::FieldDefinition;
+use crate::encode::RECORD_TYPE_COLUMN;
+use crate::parse::{Record, RecordType, RenderOptions, Value};
+use crate::sql::amount_decimal_places;
+
+/// Cantidad de registros de cada lote (y de cada grupo de filas de Parquet).
+pub const BATCH_ROWS: usize = 65_536;
+
+/// Precisión máxima de una columna `Decimal128`.
+const MAX_DECIMAL_PRECISION: u8 = 38;
+
+/// Constructor de una columna Arrow, según el tipo del campo.
+enum ColumnBuilder {
+    Decimal { builder: Decimal128Builder, precision: u8, scale: u32 },
+    Date(Date32Builder),
+    Coded(StringDictionaryBuilder<Int32Type>),
+    Text(StringBuilder),
+}
+
+impl ColumnBuilder {
+    /// Crea el constructor y el tipo Arrow de la columna de un campo.
+    fn for_field(field: &FieldDefinition) -> (ColumnBuilder, DataType) {
+        match field.tipo.as_str() {
+            "zamount" | "amount" => {
+                let scale = amount_decimal_places(field).min(MAX_DECIMAL_PRECISION as u32 - 1);
+                let precision = (field.len.max(scale as usize + 1)).min(MAX_DECIMAL_PRECISION as usize) as u8;
+                let data_type = DataType::Decimal128(precision, scale as i8);
+                let builder = Decimal128Builder::new().with_data_type(data_type.clone());
+                (ColumnBuilder::Decimal { builder, precision, scale }, data_type)
+            }
+            "date" => (ColumnBuilder::Date(Date32Builder::new()), DataType::Date32),
+            "table" => (
+                ColumnBuilder::Coded(StringDictionaryBuilder::new()),
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            ),
+            _ => (ColumnBuilder::Text(StringBuilder::new()), DataType::Utf8),
+        }
+    }
+
+    /// Agrega un valor a la columna. Los valores ausentes (líneas cortas, fechas no
+    /// informadas o columnas de otro tipo de registro) se agregan como nulos. Un monto
+    /// o una fecha inválidos no pueden guardarse en una columna numérica o de fecha, por
+    /// lo que se informan y se agregan como nulos.
+    fn append(&mut self, value: Option<&Value>, field: &FieldDefinition, options: RenderOptions) {
+        match (self, value) {
+            (ColumnBuilder::Decimal { builder, precision, scale }, Some(Value::Decimal(number))) => {
+                let mut number = *number;
+                number.rescale(*scale);
+                let mantissa = number.mantissa();
+                if mantissa.unsigned_abs() < 10u128.pow(*precision as u32) {
+                    builder.append_value(mantissa);
+                } else {
+                    warn_invalid(field, &number.to_string());
+                    builder.append_null();
+                }
+            }
+            (ColumnBuilder::Date(builder), Some(Value::Date(Some(date)))) => {
+                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("fecha válida");
+                builder.append_value(date.signed_duration_since(epoch).num_days() as i32);
+            }
+            (ColumnBuilder::Decimal { builder, .. }, value) => {
+                if let Some(Value::Text(text)) = value { warn_invalid(field, text) }
+                builder.append_null();
+            }
+            (ColumnBuilder::Date(builder), value) => {
+                if let Some(Value::Text(text)) = value { warn_invalid(field, text) }
+                builder.append_null();
+            }
+            (ColumnBuilder::Coded(builder), Some(value)) => {
+                let text = value.render(field, false, options.dont_use_tables);
+                if text.is_empty() { builder.append_null() } else { builder.append_value(text) }
+            }
+            (ColumnBuilder::Text(builder), Some(value)) => {
+                builder.append_value(value.render(field, false, options.dont_use_tables));
+            }
+            (ColumnBuilder::Coded(builder), None) => builder.append_null(),
+            (ColumnBuilder::Text(builder), None) => builder.append_null(),
+        }
+    }
+
+    /// Cierra los valores acumulados en un arreglo Arrow y deja la columna vacía.
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Decimal { builder, .. } => Arc::new(builder.finish()),
+            ColumnBuilder::Date(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Coded(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Text(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Informa un valor que no respeta el tipo de su campo.
+fn warn_invalid(field: &FieldDefinition, value: &str) {
+    if !value.trim().is_empty() {
+        eprintln!(
+            "Advertencia: Campo '{}' con valor inválido '{}'; se escribe nulo.",
+            field.nombre, value
+        );
+    }
+}
+
+/// Acumula registros y los entrega en lotes Arrow (`RecordBatch`) con el esquema del formato.
+///
+/// En los formatos de un solo tipo de registro cada campo es una columna con su nombre.
+/// En los multi-registro se agrega una columna `record_type` y cada campo se nombra
+/// `<tipo>_<campo>`: cada fila completa las columnas de su tipo y deja nulas las demás.
+pub struct BatchBuilder {
+    schema: SchemaRef,
+    columns: Vec<ColumnBuilder>,
+    record_type_column: Option<StringDictionaryBuilder<Int32Type>>,
+    // Índice de la primera columna de cada tipo de registro
+    offsets: Vec<usize>,
+    rows: usize,
+}
+
+impl BatchBuilder {
+    /// Crea el constructor de lotes con el esquema derivado de los tipos de registro.
+    ///
+    /// ## Argumentos
+    /// - `record_types`: Tipos de registro de la salida, con la definición de sus campos.
+    ///
+    /// ## Retorno
+    /// `BatchBuilder` - Constructor sin registros.
+    ///
+    /// ## Ejemplo
+    /// ```no_run
+    /// # use parseit::columnar::BatchBuilder;
+    /// # use parseit::config::load_config_from_paths;
+    /// # use parseit::io::DataInput;
+    /// # use parseit::parse::parse_to_records;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let schema = load_config_from_paths()?;
+    /// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+    /// let mut batches = BatchBuilder::new(&records.record_types);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(record_types: &[RecordType]) -> BatchBuilder {
+        let multi_record = record_types.len() > 1 || record_types.iter().any(|t| !t.name.is_empty());
+
+        let mut used_names = HashSet::new();
+        let mut schema_fields = Vec::new();
+        let mut columns = Vec::new();
+        let mut offsets = Vec::with_capacity(record_types.len());
+
+        let record_type_column = multi_record.then(|| {
+            used_names.insert(RECORD_TYPE_COLUMN.to_string());
+            schema_fields.push(Field::new(
+                RECORD_TYPE_COLUMN,
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+                false,
+            ));
+            StringDictionaryBuilder::new()
+        });
+
+        for record_type in record_types {
+            offsets.push(columns.len());
+            for field in record_type.fields.iter() {
+                let base_name = if multi_record {
+                    format!("{}_{}", record_type.name, field.nombre)
+                } else {
+                    field.nombre.clone()
+                };
+                // Los nombres repetidos se distinguen con un sufijo numérico
+                let mut name = base_name.clone();
+                let mut suffix = 2;
+                while !used_names.insert(name.clone()) {
+                    name = format!("{}_{}", base_name, suffix);
+                    suffix += 1;
+                }
+
+                let (builder, data_type) = ColumnBuilder::for_field(field);
+                schema_fields.push(Field::new(name, data_type, true));
+                columns.push(builder);
+            }
+        }
+
+        BatchBuilder {
+            schema: Arc::new(Schema::new(schema_fields)),
+            columns,
+            record_type_column,
+            offsets,
+            rows: 0,
+        }
+    }
+
+    /// Esquema Arrow de los lotes.
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Cantidad de registros acumulados desde el último lote.
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    /// Indica si no hay registros acumulados desde el último lote.
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// Agrega un registro al lote en curso.
+    ///
+    /// ## Argumentos
+    /// - `record`: Registro procesado.
+    /// - `record_types`: Tipos de registro con los que se creó el constructor.
+    /// - `options`: Opciones de la salida (`dont_use_tables` para los campos `table`).
+    pub fn append(&mut self, record: &Record, record_types: &[RecordType], options: RenderOptions) {
+        if let Some(builder) = self.record_type_column.as_mut() {
+            builder.append_value(&record_types[record.record_type].name);
+        }
+
+        for (type_index, record_type) in record_types.iter().enumerate() {
+            let offset = self.offsets[type_index];
+            for (field_index, field) in record_type.fields.iter().enumerate() {
+                let value = if type_index == record.record_type { record.values.get(field_index) } else { None };
+                self.columns[offset + field_index].append(value, field, options);
+            }
+        }
+
+        self.rows += 1;
+    }
+
+    /// Cierra el lote en curso con los registros acumulados.
+    ///
+    /// ## Retorno
+    /// `Result<RecordBatch, Box<dyn Error>>` - Lote con el esquema del formato.
+    ///
+    /// ## Errores
+    /// Retorna un error si las columnas no son consistentes con el esquema.
+    pub fn finish(&mut self) -> Result<RecordBatch, Box<dyn Error>> {
+        let mut arrays: Vec<ArrayRef> = Vec::with_capacity(self.columns.len() + 1);
+        if let Some(builder) = self.record_type_column.as_mut() {
+            arrays.push(Arc::new(builder.finish()));
+        }
+        arrays.extend(self.columns.iter_mut().map(|column| column.finish()));
+
+        self.rows = 0;
+        Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{Date32Type, Decimal128Type};
+    use rust_decimal::Decimal;
+
+    fn field(nombre: &str, tipo: &str, len: usize, param1: &str) -> FieldDefinition {
+        FieldDefinition {
+            nombre: nombre.to_string(),
+            len,
+            tipo: tipo.to_string(),
+            param1: param1.to_string(),
+            param2: String::new(),
+        }
+    }
+
+    fn record_type(name: &str, fields: Vec<FieldDefinition>) -> RecordType {
+        RecordType {
+            name: name.to_string(),
+            headers: fields.iter().map(|f| f.nombre.clone()).collect(),
+            widths: fields.iter().map(|f| f.len).collect(),
+            fields,
+        }
+    }
+
+    #[test]
+    fn schema_types_follow_the_field_types() {
+        let record_types = [record_type("", vec![
+            field("Importe", "zamount", 15, "2"),
+            field("Fecha", "date", 8, "%Y%m%d"),
+            field("Tipo", "table", 1, "tipo_operacion"),
+            field("Nombre", "string", 30, ""),
+            field("Nombre", "string", 30, ""),
+        ])];
+        let schema = BatchBuilder::new(&record_types).schema();
+
+        let columns: Vec<(&str, &DataType)> = schema.fields().iter().map(|f| (f.name().as_str(), f.data_type())).collect();
+        assert_eq!(columns, [
+            ("Importe", &DataType::Decimal128(15, 2)),
+            ("Fecha", &DataType::Date32),
+            ("Tipo", &DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))),
+            ("Nombre", &DataType::Utf8),
+            ("Nombre_2", &DataType::Utf8),
+        ]);
+    }
+
+    #[test]
+    fn batches_hold_typed_values_and_nulls_for_invalid_ones() {
+        let record_types = [record_type("", vec![
+            field("Importe", "zamount", 10, "2"),
+            field("Fecha", "date", 8, "%Y%m%d"),
+        ])];
+        let mut batches = BatchBuilder::new(&record_types);
+        let options = RenderOptions::default();
+
+        batches.append(&Record {
+            record_type: 0,
+            line_number: 1,
+            values: vec![Value::Decimal(Decimal::new(12345, 2)), Value::Date(NaiveDate::from_ymd_opt(1970, 1, 11))],
+        }, &record_types, options);
+        batches.append(&Record {
+            record_type: 0,
+            line_number: 2,
+            values: vec![Value::Text("12a".to_string()), Value::Date(None)],
+        }, &record_types, options);
+        assert_eq!(batches.len(), 2);
+
+        let batch = batches.finish().unwrap();
+        assert!(batches.is_empty());
+        let amounts = batch.column(0).as_primitive::<Decimal128Type>();
+        let dates = batch.column(1).as_primitive::<Date32Type>();
+        assert_eq!((amounts.value(0), dates.value(0)), (12345, 10));
+        assert!(amounts.is_null(1) && dates.is_null(1));
+    }
+
+    #[test]
+    fn multi_record_batches_prefix_columns_with_the_record_type() {
+        let record_types = [
+            record_type("cabecera", vec![field("Periodo", "string", 6, "")]),
+            record_type("detalle", vec![field("Importe", "zamount", 10, "2")]),
+        ];
+        let mut batches = BatchBuilder::new(&record_types);
+        let names: Vec<String> = batches.schema().fields().iter().map(|f| f.name().clone()).collect();
+        assert_eq!(names, [RECORD_TYPE_COLUMN, "cabecera_Periodo", "detalle_Importe"]);
+
+        batches.append(&Record { record_type: 0, line_number: 1, values: vec![Value::Text("202310".to_string())] },
+            &record_types, RenderOptions::default());
+        batches.append(&Record { record_type: 1, line_number: 2, values: vec![Value::Decimal(Decimal::new(100, 2))] },
+            &record_types, RenderOptions::default());
+        let batch = batches.finish().unwrap();
+
+        assert_eq!(batch.column(1).as_string::<i32>().value(0), "202310");
+        assert!(batch.column(1).is_null(1));
+        assert!(batch.column(2).is_null(0));
+        assert_eq!(batch.column(2).as_primitive::<Decimal128Type>().value(1), 100);
+    }
+}
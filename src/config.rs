@@ -4,40 +4,407 @@
 //! Proporciona funciones para cargar la configuración desde rutas específicas y calcular longitudes de formatos.
 //! También define constantes relacionadas con la configuración.
 //! 
+use chrono::NaiveDate;
 use serde::Deserialize;
-use std::collections::HashMap;
+use crate::encoding::Encoding;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fmt::Write as _;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 /// Nombre del archivo de configuración esperado.
-/// Se busca en el CWD y en el directorio del ejecutable.
+/// Se busca en el directorio del ejecutable, en el directorio de configuración del
+/// usuario y en el proyecto (ver `config_layer_paths`).
 pub const CONFIG_FILE: &str = "parseit.toon";
 
+/// Subdirectorio de parseit dentro del directorio de configuración del usuario
+/// (ej: `~/.config/parseit` en Linux, `%APPDATA%\parseit` en Windows).
+pub const USER_CONFIG_DIR: &str = "parseit";
+
+/// Tipo del campo que, en lugar de un campo, inserta los de un grupo de `field_groups`
+/// (el nombre del grupo se indica en `param1`).
+///
+/// Estos pseudo-campos se declaran con longitud 0 y `ConfigSchema::resolve` los reemplaza
+/// por los campos del grupo, por lo que no llegan a los formatos resueltos: las reglas de
+/// `check_config` que revisan campos no deben considerarlos (un campo de longitud 0 o de
+/// tipo desconocido sólo puede ser un error). Los grupos que usa cada formato quedan
+/// registrados en `ConfigSchema::used_field_groups`.
+pub const FIELD_GROUP_TYPE: &str = "group";
+
+/// Separador entre el nombre de un formato y su versión (ej: `compras-comprobantes@rg3685-v2`).
+pub const VERSION_SEPARATOR: char = '@';
+
+/// Patrón de las fechas de vigencia de los formatos (`valid_from` y `valid_to`).
+pub const VALIDITY_DATE_PATTERN: &str = "%Y-%m-%d";
+
 /// Estructura que representa el esquema de configuración completo.
 /// - formatos: Mapa de nombres de formatos a sus definiciones.
 /// - tablas: Mapa de nombres de tablas a sus datos (no usado directamente aquí).
 /// - atajos: Mapa de atajos a sus valores (no usado directamente aquí).
-#[derive(Debug, Deserialize)]
+/// - include: Archivos, directorios o patrones (ej: "regimenes/*.toon") cuyo contenido se
+///   incorpora al del archivo (ver `load_config`). Las rutas son relativas al archivo.
+/// - field_groups: Grupos de campos con nombre que los formatos reutilizan con un campo de
+///   tipo `group` (ver `ConfigSchema::resolve`).
+/// - format_sources: Archivo del que proviene cada formato (no se lee del archivo).
+/// - table_sources: Archivo del que proviene cada tabla (no se lee del archivo).
+/// - field_group_sources: Archivo del que proviene cada grupo de campos (no se lee del archivo).
+/// - layers: Archivos cargados, de menor a mayor prioridad (no se lee del archivo).
+/// - used_field_groups: Grupos de campos que usa algún formato, directamente o desde otro
+///   grupo; lo completa `resolve` (no se lee del archivo).
+///
+/// Las secciones son opcionales, así un archivo puede agregar sólo formatos o
+/// sólo tablas a los de las capas anteriores.
+#[derive(Debug, Default, Deserialize)]
 pub struct ConfigSchema {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
     pub formats: HashMap<String, FormatDefinition>,
-    #[allow(dead_code)]
+    #[serde(default)]
     pub tables: HashMap<String, HashMap<String, String>>,
-    #[allow(dead_code)]
+    #[serde(default)]
     pub shortcuts: HashMap<String, String>,
+    #[serde(default)]
+    pub field_groups: HashMap<String, Vec<FieldDefinition>>,
+    #[serde(skip)]
+    pub format_sources: HashMap<String, PathBuf>,
+    #[serde(skip)]
+    pub table_sources: HashMap<String, PathBuf>,
+    #[serde(skip)]
+    pub field_group_sources: HashMap<String, PathBuf>,
+    #[serde(skip)]
+    pub layers: Vec<ConfigLayer>,
+    #[serde(skip)]
+    pub used_field_groups: HashSet<String>,
+}
+
+/// Archivo de configuración cargado como una capa del esquema.
+/// - scope: Origen de la capa: `sistema` (junto al ejecutable), `usuario`, `proyecto`
+///   o `--config` (indicado en la línea de comandos).
+/// - path: Ruta al archivo.
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    pub scope: &'static str,
+    pub path: PathBuf,
+}
+
+impl ConfigSchema {
+    /// Agrega una capa al esquema: los formatos, tablas, atajos y grupos de campos de `layer`
+    /// se suman a los existentes y reemplazan a los que tienen el mismo nombre.
+    ///
+    /// ## Argumentos
+    /// - `layer`: Esquema cargado de la capa (ver `load_config`).
+    ///
+    /// ## Ejemplo
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use parseit::config::load_config;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut schema = load_config(Path::new("/opt/parseit/parseit.toon"))?;
+    /// schema.merge(load_config(Path::new("empresa.toon"))?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn merge(&mut self, layer: ConfigSchema) {
+        self.formats.extend(layer.formats);
+        self.tables.extend(layer.tables);
+        self.shortcuts.extend(layer.shortcuts);
+        self.field_groups.extend(layer.field_groups);
+        self.format_sources.extend(layer.format_sources);
+        self.table_sources.extend(layer.table_sources);
+        self.field_group_sources.extend(layer.field_group_sources);
+        self.layers.extend(layer.layers);
+    }
+
+    /// Incorpora el contenido de un archivo incluido. A diferencia de `merge`, un formato,
+    /// una tabla o un grupo de campos que ya existe con el mismo nombre es un error.
+    ///
+    /// ## Argumentos
+    /// - `included`: Esquema cargado del archivo incluido.
+    ///
+    /// ## Errores
+    /// Retorna un error que indica los dos archivos en los que se define el formato, la
+    /// tabla o el grupo repetido.
+    fn merge_include(&mut self, included: ConfigSchema) -> Result<(), Box<dyn Error>> {
+        let duplicate = |subject: String, first: Option<&PathBuf>, second: Option<&PathBuf>| -> Box<dyn Error> {
+            let display = |path: Option<&PathBuf>| path.map(|p| p.display().to_string()).unwrap_or_default();
+            format!("{} en '{}' y en '{}'.", subject, display(first), display(second)).into()
+        };
+
+        for name in included.formats.keys() {
+            if self.formats.contains_key(name) {
+                return Err(duplicate(
+                    format!("El formato '{}' está definido", name),
+                    self.format_sources.get(name), included.format_sources.get(name)
+                ));
+            }
+        }
+        for name in included.tables.keys() {
+            if self.tables.contains_key(name) {
+                return Err(duplicate(
+                    format!("La tabla '{}' está definida", name),
+                    self.table_sources.get(name), included.table_sources.get(name)
+                ));
+            }
+        }
+        for name in included.field_groups.keys() {
+            if self.field_groups.contains_key(name) {
+                return Err(duplicate(
+                    format!("El grupo de campos '{}' está definido", name),
+                    self.field_group_sources.get(name), included.field_group_sources.get(name)
+                ));
+            }
+        }
+
+        self.formats.extend(included.formats);
+        self.tables.extend(included.tables);
+        self.shortcuts.extend(included.shortcuts);
+        self.field_groups.extend(included.field_groups);
+        self.format_sources.extend(included.format_sources);
+        self.table_sources.extend(included.table_sources);
+        self.field_group_sources.extend(included.field_group_sources);
+        Ok(())
+    }
+
+    /// Resuelve la herencia entre formatos (`extends`) y expande los grupos de campos, de
+    /// modo que cada formato quede con su diseño completo. Se llama una vez combinadas
+    /// todas las capas, así un formato puede extender a uno de otro archivo.
+    ///
+    /// Un formato que extiende a otro hereda su categoría, delimitador, codificación y
+    /// campo de fecha si no los informa; la vigencia no se hereda. Sus campos reemplazan
+    /// a los del padre con el mismo nombre y los demás se agregan al final; lo mismo ocurre
+    /// con los tipos de registro. Un formato que no informa campos ni registros hereda todo
+    /// el diseño.
+    ///
+    /// ## Errores
+    /// Retorna un error si un formato extiende a uno que no existe, si usa un grupo que no
+    /// existe o si la herencia o los grupos forman un ciclo.
+    ///
+    /// ## Ejemplo
+    /// ```
+    /// # use parseit::config::ConfigSchema;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut schema: ConfigSchema = toon_format::decode_default(r#"
+    /// field_groups:
+    ///   comprobante[2]{nombre,len,tipo,param1,param2}:
+    ///     "Punto de venta",5,string,"",""
+    ///     "Número",20,string,"",""
+    /// formats:
+    ///   ventas:
+    ///     category: Ejemplos
+    ///     delimiter: ""
+    ///     fields[2]{nombre,len,tipo,param1,param2}:
+    ///       "Fecha",8,date,"%Y%m%d",""
+    ///       "Comprobante",0,group,comprobante,""
+    ///   ventas-utf8:
+    ///     extends: ventas
+    ///     encoding: utf-8
+    /// "#)?;
+    /// schema.resolve()?;
+    /// assert_eq!(schema.formats["ventas-utf8"].fields.len(), 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut names: Vec<String> = self.formats.keys().cloned().collect();
+        names.sort();
+
+        let mut resolved: Vec<String> = Vec::new();
+        for name in names {
+            self.resolve_format(&name, &mut Vec::new(), &mut resolved)?;
+        }
+        Ok(())
+    }
+
+    /// Resuelve un formato, resolviendo antes al formato que extiende.
+    ///
+    /// ## Argumentos
+    /// - `name`: Nombre del formato.
+    /// - `stack`: Formatos que se están resolviendo, para detectar ciclos.
+    /// - `resolved`: Formatos ya resueltos.
+    fn resolve_format(&mut self, name: &str, stack: &mut Vec<String>, resolved: &mut Vec<String>) -> Result<(), Box<dyn Error>> {
+        if resolved.iter().any(|r| r == name) {
+            return Ok(());
+        }
+        if let Some(start) = stack.iter().position(|s| s == name) {
+            let cycle: Vec<&str> = stack[start..].iter().map(String::as_str).chain([name]).collect();
+            return Err(format!("Herencia circular entre formatos: {}", cycle.join(" → ")).into());
+        }
+
+        let Some(format_def) = self.formats.get(name) else { return Ok(()) };
+        let parent_name = format_def.extends.clone();
+        let in_format = |e: String| -> Box<dyn Error> { format!("Formato '{}': {}", name, e).into() };
+
+        let used = &mut self.used_field_groups;
+        let mut fields = expand_field_groups(&format_def.fields, &self.field_groups, &mut Vec::new(), used).map_err(in_format)?;
+        let mut records = Vec::new();
+        for record in format_def.records.iter() {
+            records.push(RecordDefinition {
+                fields: expand_field_groups(&record.fields, &self.field_groups, &mut Vec::new(), used).map_err(in_format)?,
+                ..record.clone()
+            });
+        }
+
+        let (mut category, mut delimiter, mut encoding, mut date_field) = (
+            format_def.category.clone(), format_def.delimiter.clone(), format_def.declared_encoding, format_def.date_field.clone()
+        );
+
+        if !parent_name.is_empty() {
+            if !self.formats.contains_key(&parent_name) {
+                return Err(format!("El formato '{}' extiende a '{}', que no existe.", name, parent_name).into());
+            }
+            stack.push(name.to_string());
+            self.resolve_format(&parent_name, stack, resolved)?;
+            stack.pop();
+
+            let parent = &self.formats[&parent_name];
+            if category.is_empty() {
+                category = parent.category.clone();
+            }
+            if delimiter.is_empty() {
+                delimiter = parent.delimiter.clone();
+            }
+            if encoding.is_none() {
+                encoding = Some(parent.encoding);
+            }
+            if date_field.is_empty() {
+                date_field = parent.date_field.clone();
+            }
+            fields = override_by_name(&parent.fields, fields, |f| &f.nombre);
+            records = override_by_name(&parent.records, records, |r| &r.name);
+        }
+
+        if let Some(format_def) = self.formats.get_mut(name) {
+            format_def.category = category;
+            format_def.delimiter = delimiter;
+            format_def.encoding = encoding.unwrap_or_default();
+            format_def.date_field = date_field;
+            format_def.fields = fields;
+            format_def.records = records;
+        }
+        resolved.push(name.to_string());
+        Ok(())
+    }
+}
+
+/// Combina los elementos heredados con los propios: cada elemento propio reemplaza, en su
+/// posición, al heredado con el mismo nombre; los demás se agregan al final.
+fn override_by_name<T: Clone>(inherited: &[T], own: Vec<T>, name: impl Fn(&T) -> &String) -> Vec<T> {
+    let mut combined = inherited.to_vec();
+    for item in own {
+        match combined.iter().position(|c| name(c) == name(&item)) {
+            Some(index) => combined[index] = item,
+            None => combined.push(item),
+        }
+    }
+    combined
+}
+
+/// Reemplaza cada campo de tipo `group` por los campos del grupo cuyo nombre indica
+/// `param1`. Un grupo puede a su vez usar otros grupos.
+///
+/// ## Argumentos
+/// - `fields`: Campos a expandir.
+/// - `groups`: Grupos de campos del esquema.
+/// - `stack`: Grupos que se están expandiendo, para detectar ciclos.
+/// - `used`: Grupos usados, al que se agregan los que se expanden.
+///
+/// ## Retorno
+/// `Result<Vec<FieldDefinition>, String>` - Campos expandidos o descripción del error.
+fn expand_field_groups(
+    fields: &[FieldDefinition],
+    groups: &HashMap<String, Vec<FieldDefinition>>,
+    stack: &mut Vec<String>,
+    used: &mut HashSet<String>,
+    ) -> Result<Vec<FieldDefinition>, String> {
+
+    let mut expanded = Vec::with_capacity(fields.len());
+    for field in fields {
+        if field.tipo != FIELD_GROUP_TYPE {
+            expanded.push(field.clone());
+            continue;
+        }
+
+        let group_name = &field.param1;
+        if let Some(start) = stack.iter().position(|s| s == group_name) {
+            let cycle: Vec<&str> = stack[start..].iter().map(String::as_str).chain([group_name.as_str()]).collect();
+            return Err(format!("Los grupos de campos se usan en forma circular: {}", cycle.join(" → ")));
+        }
+        let group = groups.get(group_name)
+            .ok_or_else(|| format!("El grupo de campos '{}' (campo '{}') no existe.", group_name, field.nombre))?;
+
+        used.insert(group_name.clone());
+        stack.push(group_name.clone());
+        expanded.extend(expand_field_groups(group, groups, stack, used)?);
+        stack.pop();
+    }
+    Ok(expanded)
 }
 
 /// Definición de un formato específico.
 /// - category: Categoría del formato (no usado directamente aquí).
-/// - delimiter: Delimitador utilizado en el formato (no usado directamente aquí).
+/// - delimiter: Delimitador de campos. Si está vacío el formato es de longitud fija;
+///   si no, cada línea se divide por este delimitador y `len` es el ancho máximo del campo.
 /// - fields: Vector de definiciones de campos que componen el formato.
+/// - records: Tipos de registro de un formato multi-registro (cabecera, detalle, pie...).
+///   Si se informa, cada línea se interpreta con el primer tipo cuyo discriminador
+///   coincida y `fields` no se usa.
+/// - encoding: Codificación de los archivos de datos, ya resuelta (ver
+///   `ConfigSchema::resolve`): la que declara el formato, la del formato que extiende o
+///   windows-1252. Las posiciones de los campos se cuentan en bytes, antes de decodificar.
+/// - declared_encoding: Codificación que declara el formato en el archivo (`encoding`),
+///   `None` si no la informa.
+/// - extends: Formato del que hereda el diseño (ver `ConfigSchema::resolve`); vacío si no
+///   hereda de ninguno.
+/// - valid_from, valid_to: Vigencia de una versión del formato (`AAAA-MM-DD`, ambas
+///   inclusive); vacías si no tiene límite. La versión se indica en el nombre del formato,
+///   después de `VERSION_SEPARATOR`.
+/// - date_field: Campo de fecha que indica a qué versión corresponde un archivo
+///   (ver `select_format_version`).
 #[derive(Debug, Deserialize)]
 pub struct FormatDefinition {
-    #[allow(dead_code)]
+    #[serde(default)]
     pub category: String,
-    #[allow(dead_code)]
+    #[serde(default)]
     pub delimiter: String,
+    #[serde(skip)]
+    pub encoding: Encoding,
+    #[serde(default, rename = "encoding")]
+    pub declared_encoding: Option<Encoding>,
+    #[serde(default)]
+    pub extends: String,
+    #[serde(default)]
+    pub valid_from: String,
+    #[serde(default)]
+    pub valid_to: String,
+    #[serde(default)]
+    pub date_field: String,
+    #[serde(default)]
     pub fields: Vec<FieldDefinition>, 
+    #[serde(default)]
+    pub records: Vec<RecordDefinition>,
+}
+
+/// Definición de un tipo de registro dentro de un formato multi-registro.
+/// - name: Nombre del tipo de registro (ej: "cabecera", "detalle").
+/// - prefix: Valor fijo que identifica al tipo de registro (vacío si no se usa).
+/// - offset: Posición (en bytes, desde 0) donde debe aparecer `prefix`.
+/// - length: Longitud de línea que identifica al tipo de registro (opcional).
+/// - fields: Vector de definiciones de campos del tipo de registro.
+///
+/// Un registro sin `prefix` ni `length` coincide con cualquier línea, por lo que
+/// sirve como tipo por defecto si se declara al final.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub length: Option<usize>,
+    pub fields: Vec<FieldDefinition>,
 }
 
 /// Definición de un campo dentro de un formato
@@ -45,104 +412,1095 @@ pub struct FormatDefinition {
 /// - len: Longitud del campo
 /// - tipo: Tipo de dato (ej: string, integer, etc.)
 /// - param1, param2: Parámetros adicionales (dependiendo del tipo)
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct FieldDefinition {
     pub nombre: String,
     pub len: usize,
-    #[allow(dead_code)]
     pub tipo: String,
-    #[allow(dead_code)]
     pub param1: String,
-    #[allow(dead_code)]
     pub param2: String,
 }
 
-/// Intenta cargar el archivo de configuración primero desde el CWD, luego desde el directorio del ejecutable.
+/// Carga un archivo de configuración junto con los archivos de su lista `include`.
+/// Cada formato y tabla queda registrado con el archivo del que proviene (ver
+/// `ConfigSchema::format_sources`).
+///
+/// Cada entrada de `include` es un archivo, un directorio (se incluyen sus archivos
+/// `.toon`) o un patrón, relativo al directorio del archivo que la declara. Los archivos
+/// incluidos pueden a su vez incluir otros; un archivo que ya se incluyó (por ejemplo,
+/// unas tablas compartidas por varios regímenes) no se vuelve a cargar. Por último se
+/// resuelven la herencia y los grupos de campos (ver `ConfigSchema::resolve`).
 /// 
 /// ## Argumentos
 /// - `path`: Ruta al archivo de configuración.
-/// - Retorna un `ConfigSchema` si se carga exitosamente, o un error si falla.
-    /// 
+/// 
 /// ## Retorno
 /// `Result<ConfigSchema, Box<dyn Error>>` - Esquema de configuración o error.
 /// 
 /// ## Errores
-/// Retorna un error si el archivo no se puede leer o si el contenido no es válido
-/// de acuerdo al esquema esperado.
+/// Retorna un error si un archivo no se puede leer o su contenido no es válido de
+/// acuerdo al esquema esperado, si un archivo incluido no existe, si las inclusiones
+/// forman un ciclo, si un formato, una tabla o un grupo se define en más de un archivo
+/// o si la herencia de los formatos no se puede resolver.
 /// 
 /// ## Ejemplo
-/// ```
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::config::load_config;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// let schema = load_config(Path::new("parseit.toon"))?;
+/// # Ok(())
+/// # }
 /// ```
 pub fn load_config(path: &Path) -> Result<ConfigSchema, Box<dyn Error>> {
+    let mut schema = load_config_file(path, &mut Vec::new(), &mut Vec::new())?;
+    schema.layers = vec![ConfigLayer { scope: "--config", path: path.to_path_buf() }];
+    schema.resolve()?;
+    Ok(schema)
+}
 
-    let content = fs::read_to_string(path)?; 
+/// Carga un archivo de configuración y, recursivamente, sus inclusiones.
+///
+/// ## Argumentos
+/// - `path`: Ruta al archivo de configuración.
+/// - `stack`: Archivos (canónicos) que se están incluyendo, para detectar ciclos.
+/// - `loaded`: Archivos (canónicos) ya cargados, que no se vuelven a incluir.
+///
+/// ## Retorno
+/// `Result<ConfigSchema, Box<dyn Error>>` - Esquema del archivo y sus inclusiones.
+fn load_config_file(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    loaded: &mut Vec<PathBuf>,
+    ) -> Result<ConfigSchema, Box<dyn Error>> {
+
+    let canonical = fs::canonicalize(path)
+        .map_err(|e| format!("No se pudo abrir el archivo de configuración '{}': {}", path.display(), e))?;
+
+    if let Some(start) = stack.iter().position(|p| *p == canonical) {
+        let cycle: Vec<String> = stack[start..].iter().chain([&canonical]).map(|p| p.display().to_string()).collect();
+        return Err(format!("Inclusión circular en la configuración: {}", cycle.join(" → ")).into());
+    }
+    if loaded.contains(&canonical) {
+        return Ok(ConfigSchema::default());
+    }
+    loaded.push(canonical.clone());
+
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("No se pudo leer el archivo de configuración '{}': {}", path.display(), e))?; 
     let content_clean = content.trim_start_matches('\u{feff}'); 
-    let schema: ConfigSchema = toon_format::decode_default(content_clean)?;
+    let mut schema: ConfigSchema = toon_format::decode_default(content_clean)
+        .map_err(|e| format!("Error en el archivo de configuración '{}': {}", path.display(), e))?;
+
+    schema.format_sources = schema.formats.keys().map(|name| (name.clone(), path.to_path_buf())).collect();
+    schema.table_sources = schema.tables.keys().map(|name| (name.clone(), path.to_path_buf())).collect();
+    schema.field_group_sources = schema.field_groups.keys().map(|name| (name.clone(), path.to_path_buf())).collect();
+
+    stack.push(canonical);
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    for entry in std::mem::take(&mut schema.include) {
+        for included_path in expand_include(base_dir, &entry, path)? {
+            let included = load_config_file(&included_path, stack, loaded)?;
+            schema.merge_include(included)?;
+        }
+    }
+    stack.pop();
 
     Ok(schema)
 }
 
-/// Intenta cargar el archivo de configuración desde múltiples rutas posibles.
-/// 
+/// Expande una entrada de `include` en los archivos que representa, ordenados.
+///
 /// ## Argumentos
-/// - Primero intenta desde el directorio actual de ejecución (CWD).
-/// - Luego intenta desde el directorio del ejecutable.
-/// - Retorna un `ConfigSchema` si se carga exitosamente, o un error si no se encuentra.
-/// 
+/// - `base_dir`: Directorio del archivo que declara la inclusión.
+/// - `entry`: Archivo, directorio o patrón a incluir.
+/// - `source`: Archivo que declara la inclusión (para los mensajes).
+///
 /// ## Retorno
-/// `Result<ConfigSchema, Box<dyn Error>>` - Esquema de configuración o error.
-/// 
+/// `Result<Vec<PathBuf>, Box<dyn Error>>` - Archivos a incluir. Un patrón que no coincide
+/// con ningún archivo se informa como advertencia y no incluye nada.
+///
 /// ## Errores
-/// Retorna un error si no se encuentra el archivo de configuración en ninguna de las rutas.
-/// 
-/// #Ejemplo
-/// ```
-/// let schema = load_config_from_paths()?;
-/// ```
-pub fn load_config_from_paths() -> Result<ConfigSchema, Box<dyn Error>> {
-    
-    // Lista de rutas a intentar, en orden de prioridad.
-    let mut search_paths: Vec<PathBuf> = Vec::new();
+/// Retorna un error si el archivo indicado no existe o si el patrón es inválido.
+fn expand_include(base_dir: &Path, entry: &str, source: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    // Se eliminan los `..` de la ruta para que los mensajes y el origen de cada formato sean legibles
+    let mut target = PathBuf::new();
+    for component in base_dir.join(entry).components() {
+        match component {
+            std::path::Component::ParentDir if matches!(target.components().next_back(), Some(std::path::Component::Normal(_))) => {
+                target.pop();
+            }
+            component => target.push(component),
+        }
+    }
+
+    if target.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(&target)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "toon"))
+            .collect();
+        files.sort();
+        return Ok(files);
+    }
 
-    // 1. Ruta Actual de Ejecución (Current Working Directory - CWD)
-    if let Ok(cwd) = std::env::current_dir() {
-        let config_path = cwd.join(CONFIG_FILE);
-        search_paths.push(config_path);
+    if !entry.contains(['*', '?', '[']) || target.exists() {
+        if !target.is_file() {
+            return Err(format!(
+                "El archivo '{}' incluido desde '{}' no existe.", target.display(), source.display()
+            ).into());
+        }
+        return Ok(vec![target]);
     }
-    
-    // 2. Ruta del Ejecutable (Donde está instalado el binario)
+
+    let mut files: Vec<PathBuf> = glob::glob(&target.to_string_lossy())
+        .map_err(|e| format!("Patrón de inclusión inválido '{}' en '{}': {}", entry, source.display(), e))?
+        .filter_map(Result::ok)
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+
+    if files.is_empty() {
+        eprintln!(
+            "Advertencia: El patrón de inclusión '{}' de '{}' no coincide con ningún archivo.", entry, source.display()
+        );
+    }
+    Ok(files)
+}
+
+/// Devuelve los archivos de configuración existentes, en orden de prioridad creciente:
+///
+/// 1. `sistema`: junto al ejecutable (los formatos que se distribuyen con parseit).
+/// 2. `usuario`: en el directorio de configuración del usuario (`USER_CONFIG_DIR`).
+/// 3. `proyecto`: el más cercano en el directorio actual o en sus ancestros.
+/// 4. `--config`: el indicado explícitamente.
+///
+/// Un mismo archivo se incluye una sola vez, con la prioridad más alta que le corresponda.
+///
+/// ## Argumentos
+/// - `explicit`: Archivo indicado con `--config` (`None` si no se indicó).
+///
+/// ## Retorno
+/// `Result<Vec<ConfigLayer>, Box<dyn Error>>` - Capas a cargar.
+///
+/// ## Errores
+/// Retorna un error si el archivo `explicit` no existe.
+pub fn config_layer_paths(explicit: Option<&Path>) -> Result<Vec<ConfigLayer>, Box<dyn Error>> {
+    let mut candidates: Vec<ConfigLayer> = Vec::new();
+
     if let Ok(mut exe_path) = std::env::current_exe() {
         // Obtenemos el directorio padre (eliminamos el nombre del ejecutable)
         if exe_path.pop() {
-            let config_path = exe_path.join(CONFIG_FILE);
-            search_paths.push(config_path);
+            candidates.push(ConfigLayer { scope: "sistema", path: exe_path.join(CONFIG_FILE) });
         }
     }
 
-    // 3. Iterar y cargar la configuración
-    for path in &search_paths {
-        if path.exists() {
-            return crate::config::load_config(path); // Asume que load_config ahora toma PathBuf o &Path
+    if let Some(config_dir) = dirs_next::config_dir() {
+        candidates.push(ConfigLayer { scope: "usuario", path: config_dir.join(USER_CONFIG_DIR).join(CONFIG_FILE) });
+    }
+
+    if let Ok(cwd) = std::env::current_dir()
+        && let Some(path) = cwd.ancestors().map(|dir| dir.join(CONFIG_FILE)).find(|path| path.is_file()) {
+        candidates.push(ConfigLayer { scope: "proyecto", path });
+    }
+
+    if let Some(path) = explicit {
+        if !path.is_file() {
+            return Err(format!("No existe el archivo de configuración '{}'.", path.display()).into());
         }
+        candidates.push(ConfigLayer { scope: "--config", path: path.to_path_buf() });
+    }
+
+    // Se descartan los inexistentes y las apariciones repetidas de menor prioridad
+    let mut layers: Vec<ConfigLayer> = Vec::new();
+    for candidate in candidates.into_iter().filter(|c| c.path.is_file()) {
+        let canonical = fs::canonicalize(&candidate.path).ok();
+        layers.retain(|l| fs::canonicalize(&l.path).ok() != canonical);
+        layers.push(candidate);
+    }
+
+    Ok(layers)
+}
+
+/// Carga la configuración por capas (ver `config_layer_paths`): cada archivo agrega sus
+/// formatos, tablas y atajos a los de las capas anteriores y reemplaza a los que tienen
+/// el mismo nombre. Así, los formatos propios de una empresa pueden mantenerse en un
+/// archivo aparte de los que se distribuyen con parseit.
+/// 
+/// ## Argumentos
+/// - `explicit`: Archivo indicado con `--config` (`None` si no se indicó).
+/// 
+/// ## Retorno
+/// `Result<ConfigSchema, Box<dyn Error>>` - Esquema combinado, con el archivo del que
+/// proviene cada formato en `format_sources`.
+/// 
+/// ## Errores
+/// Retorna un error si no se encuentra ningún archivo de configuración o si alguno no
+/// se puede leer o no es válido, o si la herencia de los formatos no se puede resolver.
+/// 
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::config::load_layered_config;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_layered_config(Some(Path::new("empresa.toon")))?;
+/// for layer in schema.layers.iter() {
+///     println!("{}: {}", layer.scope, layer.path.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn load_layered_config(explicit: Option<&Path>) -> Result<ConfigSchema, Box<dyn Error>> {
+    let layers = config_layer_paths(explicit)?;
+    if layers.is_empty() {
+        return Err(format!(
+            "No se pudo encontrar el archivo de configuración '{}' en ninguna de las rutas de búsqueda.",
+            CONFIG_FILE
+        ).into());
     }
 
-    // 4. Si ninguna ruta funciona, retornar error.
-    Err(format!(
-        "No se pudo encontrar el archivo de configuración '{}' en ninguna de las rutas de búsqueda.",
-        CONFIG_FILE
-    ).into())
+    let mut schema = ConfigSchema::default();
+    for layer in layers {
+        let mut layer_schema = load_config_file(&layer.path, &mut Vec::new(), &mut Vec::new())?;
+        layer_schema.layers = vec![layer];
+        schema.merge(layer_schema);
+    }
+
+    // La herencia se resuelve con todas las capas, así un formato puede extender a otro
+    // de una capa anterior
+    schema.resolve()?;
+    Ok(schema)
+}
+
+/// Carga la configuración por capas, sin un archivo explícito.
+/// Equivale a `load_layered_config(None)`.
+/// 
+/// ## Retorno
+/// `Result<ConfigSchema, Box<dyn Error>>` - Esquema de configuración o error.
+/// 
+/// ## Errores
+/// Retorna un error si no se encuentra el archivo de configuración en ninguna de las rutas.
+/// 
+/// #Ejemplo
+/// ```no_run
+/// # use parseit::config::load_config_from_paths;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn load_config_from_paths() -> Result<ConfigSchema, Box<dyn Error>> {
+    load_layered_config(None)
 }
 
 /// Calcula la longitud total de un formato sumando las longitudes de sus campos.
 /// #Arguments
 /// - `fields`: Vector de definiciones de campos del formato.
 /// - Retorna la suma de las longitudes de los campos.
+///
 /// #Ejemplo
 /// ```
-/// let total_length = calculate_format_length(&fields);
+/// # use parseit::config::{ConfigSchema, calculate_format_length};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema: ConfigSchema = toon_format::decode_default(r#"
+/// formats:
+///   sample:
+///     category: Ejemplos
+///     delimiter: ""
+///     fields[2]{nombre,len,tipo,param1,param2}:
+///       "Fecha",8,date,"%Y%m%d",""
+///       "Importe",34,zamount,"2",""
+/// "#)?;
+/// let total_length = calculate_format_length(&schema.formats["sample"].fields);
 /// assert_eq!(total_length, 42);
+/// # Ok(())
+/// # }
 /// ```
 pub fn calculate_format_length(fields: &[FieldDefinition]) -> usize {
     fields.iter().map(|f| f.len).sum()
 }
+
+/// Devuelve los tipos de registro de un formato como pares (nombre, campos).
+/// Un formato de un solo tipo de registro devuelve un único par con nombre vacío.
+///
+/// ## Argumentos
+/// - `format_def`: Definición del formato.
+///
+/// ## Ejemplo
+/// ```
+/// # use std::path::Path;
+/// # use parseit::config::{calculate_format_length, load_config, record_layouts};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config(Path::new("samples/multi-registro.toon"))?;
+/// for (name, fields) in record_layouts(&schema.formats["sample-multi"]) {
+///     println!("{}: {} bytes", name, calculate_format_length(fields));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn record_layouts(format_def: &FormatDefinition) -> Vec<(&str, &[FieldDefinition])> {
+    if format_def.records.is_empty() {
+        return vec![("", format_def.fields.as_slice())];
+    }
+
+    format_def.records.iter()
+        .map(|r| (r.name.as_str(), r.fields.as_slice()))
+        .collect()
+}
+
+/// Separa el nombre de un formato de su versión.
+///
+/// ## Argumentos
+/// - `name`: Nombre del formato, con o sin versión.
+///
+/// ## Retorno
+/// `(&str, Option<&str>)` - Nombre base y versión (`None` si el nombre no tiene versión).
+///
+/// ## Ejemplo
+/// ```
+/// # use parseit::config::split_format_name;
+/// assert_eq!(split_format_name("compras-comprobantes@rg3685-v2"), ("compras-comprobantes", Some("rg3685-v2")));
+/// ```
+pub fn split_format_name(name: &str) -> (&str, Option<&str>) {
+    match name.split_once(VERSION_SEPARATOR) {
+        Some((base, version)) => (base, Some(version)),
+        None => (name, None),
+    }
+}
+
+/// Interpreta una fecha de vigencia (`valid_from` o `valid_to`).
+///
+/// ## Retorno
+/// `Result<Option<NaiveDate>, String>` - La fecha, `None` si está vacía (sin límite) o
+/// la descripción del error.
+fn parse_validity_date(value: &str) -> Result<Option<NaiveDate>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(value, VALIDITY_DATE_PATTERN)
+        .map(Some)
+        .map_err(|_| format!("'{}' no es una fecha AAAA-MM-DD", value))
+}
+
+impl FormatDefinition {
+    /// Indica si el formato está vigente en una fecha. Una fecha de vigencia que no se
+    /// puede interpretar se toma como sin límite (ver la regla `vigencia` de `check_config`).
+    ///
+    /// ## Ejemplo
+    /// ```
+    /// # use parseit::NaiveDate;
+    /// # use parseit::config::ConfigSchema;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let schema: ConfigSchema = toon_format::decode_default(r#"
+    /// formats:
+    ///   compras-comprobantes@rg3685-v2:
+    ///     category: Afip.Rg3685
+    ///     delimiter: ""
+    ///     valid_from: "2024-01-01"
+    /// "#)?;
+    /// let version = &schema.formats["compras-comprobantes@rg3685-v2"];
+    /// assert!(version.is_effective(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()));
+    /// assert!(!version.is_effective(NaiveDate::from_ymd_opt(2023, 12, 31).unwrap()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_effective(&self, date: NaiveDate) -> bool {
+        let from = parse_validity_date(&self.valid_from).ok().flatten();
+        let to = parse_validity_date(&self.valid_to).ok().flatten();
+        from.is_none_or(|from| from <= date) && to.is_none_or(|to| date <= to)
+    }
+
+    /// Vigencia del formato para los reportes (ej: "2024-01-01 → ..."), vacía si no tiene.
+    pub fn validity(&self) -> String {
+        if self.valid_from.is_empty() && self.valid_to.is_empty() {
+            return String::new();
+        }
+        let bound = |value: &str| if value.is_empty() { "...".to_string() } else { value.to_string() };
+        format!("{} → {}", bound(&self.valid_from), bound(&self.valid_to))
+    }
+}
+
+/// Devuelve las versiones de un formato, ordenadas por inicio de vigencia.
+///
+/// ## Argumentos
+/// - `schema`: Esquema de configuración cargado.
+/// - `base_name`: Nombre del formato, sin versión.
+///
+/// ## Retorno
+/// `Vec<&str>` - Nombres completos (`nombre@versión`) de las versiones; vacío si el
+/// formato no tiene versiones.
+pub fn format_versions<'a>(schema: &'a ConfigSchema, base_name: &str) -> Vec<&'a str> {
+    let mut versions: Vec<&str> = schema.formats.keys()
+        .map(String::as_str)
+        .filter(|name| matches!(split_format_name(name), (base, Some(_)) if base == base_name))
+        .collect();
+    versions.sort_by_key(|name| (&schema.formats[*name].valid_from, *name));
+    versions
+}
+
+/// Tipos de campo (`tipo`) admitidos en las definiciones de campos.
+pub const FIELD_TYPES: &[&str] = &["string", "numeric", "amount", "zamount", "table", "date"];
+
+/// Gravedad de un problema de la configuración.
+/// - Error: El formato no se puede procesar como fue declarado.
+/// - Warning: El formato es válido, pero puede dar resultados inesperados.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "advertencia",
+        })
+    }
+}
+
+/// Problema encontrado al revisar la configuración (ver `check_config`).
+/// - severity: Gravedad del problema.
+/// - format: Nombre del formato (vacío si el problema es de un grupo de campos).
+/// - location: Ubicación dentro del formato (ej: "registro detalle, campo 3 (Importe)"),
+///   vacía si el problema es del formato completo, o el grupo de campos (ej: "grupo comprobante").
+/// - rule: Regla incumplida: `sin_campos`, `tipo_desconocido`, `tabla_inexistente`,
+///   `decimales`, `nombre_duplicado`, `longitud_cero`, `patron_de_fecha`, `longitud_repetida`,
+///   `vigencia`, `campo_de_fecha`, `vigencia_superpuesta` o `grupo_sin_uso`.
+/// - detail: Descripción del problema.
+#[derive(Debug)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub format: String,
+    pub location: String,
+    pub rule: &'static str,
+    pub detail: String,
+}
+
+/// Revisa un patrón de fecha de entrada (`param1`): debe ser un patrón `strftime` válido
+/// y contener el día, el mes y el año, de modo que una fecha escrita con él se pueda
+/// volver a leer.
+///
+/// ## Retorno
+/// `Option<String>` - La descripción del problema, o `None` si el patrón es válido.
+fn check_input_date_pattern(pattern: &str) -> Option<String> {
+    if pattern.is_empty() {
+        return Some("falta el patrón de entrada de la fecha (param1)".to_string());
+    }
+
+    let sample = NaiveDate::from_ymd_opt(2000, 12, 31)?;
+    let mut formatted = String::new();
+    if write!(formatted, "{}", sample.format(pattern)).is_err() {
+        return Some(format!("'{}' no es un patrón de fecha válido (param1)", pattern));
+    }
+
+    match NaiveDate::parse_from_str(&formatted, pattern) {
+        Ok(date) if date == sample => None,
+        Ok(_) => Some(format!("'{}' no identifica una única fecha (param1)", pattern)),
+        Err(e) => Some(format!("con el patrón '{}' no se puede leer una fecha (param1): {}", pattern, e)),
+    }
+}
+
+/// Revisa los campos de un tipo de registro y agrega los problemas encontrados.
+///
+/// ## Argumentos
+/// - `format_name`: Nombre del formato.
+/// - `record_name`: Nombre del tipo de registro (vacío en los formatos de un solo tipo).
+/// - `fields`: Definiciones de campos del tipo de registro.
+/// - `schema`: Esquema de configuración (tablas de lookup).
+/// - `issues`: Problemas encontrados.
+fn check_fields(
+    format_name: &str,
+    record_name: &str,
+    fields: &[FieldDefinition],
+    schema: &ConfigSchema,
+    issues: &mut Vec<ConfigIssue>,
+    ) {
+
+    let mut push = |index: usize, field: &FieldDefinition, rule: &'static str, detail: String| {
+        let mut location = format!("campo {} ({})", index + 1, field.nombre);
+        if !record_name.is_empty() {
+            location = format!("registro {}, {}", record_name, location);
+        }
+        issues.push(ConfigIssue { severity: Severity::Error, format: format_name.to_string(), location, rule, detail });
+    };
+
+    for (index, field) in fields.iter().enumerate() {
+        if fields[..index].iter().any(|f| f.nombre == field.nombre) {
+            push(index, field, "nombre_duplicado", format!("el nombre '{}' ya se usó en otro campo", field.nombre));
+        }
+
+        if field.len == 0 {
+            push(index, field, "longitud_cero", "el campo tiene longitud 0".to_string());
+        }
+
+        match field.tipo.as_str() {
+            "table" if field.param1.is_empty() => {
+                push(index, field, "tabla_inexistente", "falta el nombre de la tabla (param1)".to_string());
+            }
+            "table" if !schema.tables.contains_key(&field.param1) => {
+                push(index, field, "tabla_inexistente", format!("la tabla '{}' no existe en tables", field.param1));
+            }
+            "zamount" | "amount" if !field.param1.is_empty() && field.param1.parse::<usize>().is_err() => {
+                push(index, field, "decimales", format!(
+                    "'{}' no es una cantidad de decimales (param1)", field.param1
+                ));
+            }
+            "date" => {
+                if let Some(detail) = check_input_date_pattern(&field.param1) {
+                    push(index, field, "patron_de_fecha", detail);
+                }
+                let mut formatted = String::new();
+                if !field.param2.is_empty()
+                    && let Some(sample) = NaiveDate::from_ymd_opt(2000, 12, 31)
+                    && write!(formatted, "{}", sample.format(&field.param2)).is_err() {
+                    push(index, field, "patron_de_fecha", format!(
+                        "'{}' no es un patrón de fecha válido (param2)", field.param2
+                    ));
+                }
+            }
+            tipo if !FIELD_TYPES.contains(&tipo) => {
+                push(index, field, "tipo_desconocido", format!(
+                    "tipo '{}' desconocido (use {})", tipo, FIELD_TYPES.join(", ")
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Revisa la vigencia y el campo de fecha de una versión de un formato y agrega los
+/// problemas encontrados.
+///
+/// ## Argumentos
+/// - `format_name`: Nombre del formato (con su versión).
+/// - `definition`: Definición del formato, ya resuelta.
+/// - `schema`: Esquema de configuración cargado (las demás versiones del formato).
+/// - `issues`: Lista a la que se agregan los problemas.
+fn check_version(format_name: &str, definition: &FormatDefinition, schema: &ConfigSchema, issues: &mut Vec<ConfigIssue>) {
+    let mut push = |severity: Severity, rule: &'static str, detail: String| {
+        issues.push(ConfigIssue { severity, format: format_name.to_string(), location: String::new(), rule, detail });
+    };
+
+    let from = parse_validity_date(&definition.valid_from);
+    let to = parse_validity_date(&definition.valid_to);
+    for (attribute, date) in [("valid_from", &from), ("valid_to", &to)] {
+        if let Err(e) = date {
+            push(Severity::Error, "vigencia", format!("{} ({})", e, attribute));
+        }
+    }
+    if let (Ok(Some(from)), Ok(Some(to))) = (&from, &to) && from > to {
+        push(Severity::Error, "vigencia", format!("valid_from ({}) es posterior a valid_to ({})", from, to));
+    }
+
+    let (base_name, _) = split_format_name(format_name);
+    let versions = format_versions(schema, base_name);
+
+    if definition.date_field.is_empty() {
+        if versions.len() > 1 {
+            push(Severity::Warning, "campo_de_fecha",
+                "sin date_field la versión sólo se puede elegir por el contenido o indicándola con -f".to_string());
+        }
+    } else {
+        let found = record_layouts(definition).iter()
+            .flat_map(|(_, fields)| fields.iter())
+            .find(|f| f.nombre == definition.date_field)
+            .map(|f| f.tipo == "date");
+        match found {
+            None => push(Severity::Error, "campo_de_fecha", format!("el campo '{}' no existe", definition.date_field)),
+            Some(false) => push(Severity::Error, "campo_de_fecha", format!("el campo '{}' no es de tipo date", definition.date_field)),
+            Some(true) => {}
+        }
+    }
+
+    // Sólo se compara con las versiones anteriores, para informar cada superposición una vez
+    let (Ok(from), Ok(to)) = (from, to) else { return };
+    for other_name in versions.iter().take_while(|v| **v != format_name) {
+        let other = &schema.formats[*other_name];
+        let (Ok(other_from), Ok(other_to)) = (parse_validity_date(&other.valid_from), parse_validity_date(&other.valid_to)) else {
+            continue;
+        };
+        let starts_before_other_ends = match (from, other_to) { (Some(a), Some(b)) => a <= b, _ => true };
+        let other_starts_before_end = match (other_from, to) { (Some(a), Some(b)) => a <= b, _ => true };
+        if starts_before_other_ends && other_starts_before_end {
+            push(Severity::Warning, "vigencia_superpuesta", format!(
+                "la vigencia ({}) se superpone con la de '{}' ({})", definition.validity(), other_name, other.validity()
+            ));
+        }
+    }
+}
+
+/// Revisa todas las definiciones de formatos y campos del esquema: tipos de campo
+/// desconocidos, tablas de lookup inexistentes, decimales no numéricos, nombres de
+/// campo (o de tipo de registro) repetidos, campos de longitud 0, patrones de fecha
+/// inválidos, formatos de longitud fija con la misma longitud de registro que otro
+/// (lo que obliga a la deducción a decidir por el contenido) y, en los formatos con
+/// versiones, fechas de vigencia inválidas o superpuestas y campos de fecha inexistentes.
+/// También informa los grupos de campos que no usa ningún formato (ver
+/// `ConfigSchema::used_field_groups`).
+///
+/// ## Argumentos
+/// - `schema`: Esquema de configuración cargado.
+///
+/// ## Retorno
+/// `Vec<ConfigIssue>` - Problemas encontrados, ordenados por nombre de formato, seguidos
+/// de los de los grupos de campos.
+///
+/// ## Ejemplo
+/// ```no_run
+/// # use parseit::config::{check_config, load_config_from_paths};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// let issues = check_config(&schema);
+/// for issue in issues.iter() {
+///     println!("{} {} {}: {}", issue.severity, issue.format, issue.location, issue.detail);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn check_config(schema: &ConfigSchema) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let mut names: Vec<&String> = schema.formats.keys().collect();
+    names.sort();
+
+    // Longitudes de registro de cada formato de longitud fija, para detectar repeticiones
+    let mut lengths: Vec<(&String, Vec<usize>)> = Vec::new();
+
+    for name in names {
+        let definition = &schema.formats[name];
+        let layouts = record_layouts(definition);
+
+        if layouts.iter().all(|(_, fields)| fields.is_empty()) {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                format: name.clone(),
+                location: String::new(),
+                rule: "sin_campos",
+                detail: "el formato no declara fields ni records".to_string(),
+            });
+            continue;
+        }
+
+        for (index, record) in definition.records.iter().enumerate() {
+            if definition.records[..index].iter().any(|r| r.name == record.name) {
+                issues.push(ConfigIssue {
+                    severity: Severity::Error,
+                    format: name.clone(),
+                    location: format!("registro {} ({})", index + 1, record.name),
+                    rule: "nombre_duplicado",
+                    detail: format!("el nombre '{}' ya se usó en otro tipo de registro", record.name),
+                });
+            }
+        }
+
+        for (record_name, fields) in layouts.iter() {
+            check_fields(name, record_name, fields, schema, &mut issues);
+        }
+
+        if split_format_name(name).1.is_some() {
+            check_version(name, definition, schema, &mut issues);
+        }
+
+        if definition.delimiter.is_empty() {
+            let mut format_lengths: Vec<usize> = layouts.iter().map(|(_, fields)| calculate_format_length(fields)).collect();
+            format_lengths.sort();
+
+            // Las versiones de un mismo formato se distinguen por la fecha (ver `select_format_version`)
+            let base_name = split_format_name(name).0;
+            if let Some((other, _)) = lengths.iter()
+                .find(|(other, l)| *l == format_lengths && split_format_name(other).0 != base_name) {
+                let text: Vec<String> = format_lengths.iter().map(|l| l.to_string()).collect();
+                issues.push(ConfigIssue {
+                    severity: Severity::Warning,
+                    format: name.clone(),
+                    location: String::new(),
+                    rule: "longitud_repetida",
+                    detail: format!(
+                        "misma longitud de registro ({} bytes) que '{}': la deducción depende del contenido de los campos",
+                        text.join("/"), other
+                    ),
+                });
+            }
+            lengths.push((name, format_lengths));
+        }
+    }
+
+    let mut unused_groups: Vec<&String> = schema.field_groups.keys()
+        .filter(|name| !schema.used_field_groups.contains(*name))
+        .collect();
+    unused_groups.sort();
+    for group in unused_groups {
+        issues.push(ConfigIssue {
+            severity: Severity::Warning,
+            format: String::new(),
+            location: format!("grupo {}", group),
+            rule: "grupo_sin_uso",
+            detail: format!("ningún formato usa el grupo de campos '{}'", group),
+        });
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Esquema de prueba a partir del contenido de un `parseit.toon`, ya resuelto.
+    fn schema_from(content: &str) -> ConfigSchema {
+        let mut schema: ConfigSchema = toon_format::decode_default(content).unwrap();
+        schema.resolve().unwrap();
+        schema
+    }
+
+    /// Escribe un archivo de configuración de prueba en `dir` y devuelve su ruta.
+    fn config_file(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Pares (regla, ubicación) de los problemas de un formato.
+    fn issues_of<'a>(issues: &'a [ConfigIssue], format: &str) -> Vec<(&'static str, &'a str)> {
+        issues.iter().filter(|i| i.format == format).map(|i| (i.rule, i.location.as_str())).collect()
+    }
+
+    #[test]
+    fn check_config_reports_each_field_rule() {
+        let schema = schema_from(r#"formats:
+  campos:
+    category: Pruebas
+    delimiter: ""
+    fields[6]{nombre,len,tipo,param1,param2}:
+      "Código",2,table,provincias,""
+      "Importe",10,zamount,dos,""
+      "Fecha",8,date,"%Y%m",""
+      "Código",3,string,"",""
+      "Relleno",0,string,"",""
+      "Otro",4,texto,"",""
+  vacio:
+    category: Pruebas
+    delimiter: ""
+"#);
+        let issues = check_config(&schema);
+
+        assert_eq!(issues_of(&issues, "campos"), vec![
+            ("tabla_inexistente", "campo 1 (Código)"),
+            ("decimales", "campo 2 (Importe)"),
+            ("patron_de_fecha", "campo 3 (Fecha)"),
+            ("nombre_duplicado", "campo 4 (Código)"),
+            ("longitud_cero", "campo 5 (Relleno)"),
+            ("tipo_desconocido", "campo 6 (Otro)"),
+        ]);
+        assert_eq!(issues_of(&issues, "vacio"), vec![("sin_campos", "")]);
+        assert!(issues.iter().all(|i| i.severity == Severity::Error));
+    }
+
+    #[test]
+    fn check_config_warns_about_fixed_formats_with_the_same_length() {
+        let schema = schema_from(r#"formats:
+  alfa:
+    category: Pruebas
+    delimiter: ""
+    fields[1]{nombre,len,tipo,param1,param2}:
+      "Nombre",10,string,"",""
+  beta:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Nombre",6,string,"",""
+      "Código",4,string,"",""
+  gamma:
+    category: Pruebas
+    delimiter: ";"
+    fields[1]{nombre,len,tipo,param1,param2}:
+      "Nombre",10,string,"",""
+"#);
+        let issues = check_config(&schema);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].format.as_str(), issues[0].rule), ("beta", "longitud_repetida"));
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert!(issues[0].detail.contains("'alfa'"));
+    }
+
+    #[test]
+    fn check_input_date_pattern_requires_day_month_and_year() {
+        assert_eq!(check_input_date_pattern("%Y%m%d"), None);
+        assert_eq!(check_input_date_pattern("%d/%m/%Y"), None);
+        assert!(check_input_date_pattern("").is_some());
+        assert!(check_input_date_pattern("%Y%m").is_some());
+        assert!(check_input_date_pattern("%Q").is_some());
+    }
+
+    #[test]
+    fn check_config_reports_duplicate_record_names() {
+        let schema = schema_from(r#"formats:
+  multi:
+    category: Pruebas
+    delimiter: ""
+    records[2]:
+      - name: detalle
+        prefix: "1"
+        fields[1]{nombre,len,tipo,param1,param2}:
+          "Tipo",1,string,"",""
+      - name: detalle
+        prefix: "2"
+        fields[1]{nombre,len,tipo,param1,param2}:
+          "Tipo",1,string,"",""
+"#);
+        let issues = check_config(&schema);
+
+        assert_eq!(issues_of(&issues, "multi"), vec![("nombre_duplicado", "registro 2 (detalle)")]);
+    }
+
+    #[test]
+    fn merge_overrides_and_extends_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = config_file(dir.path(), "sistema.toon", r#"formats:
+  ventas:
+    category: Sistema
+    delimiter: ""
+    fields[1]{nombre,len,tipo,param1,param2}:
+      "Nombre",10,string,"",""
+  compras:
+    category: Sistema
+    delimiter: ""
+    fields[1]{nombre,len,tipo,param1,param2}:
+      "Nombre",10,string,"",""
+tables:
+  provincias:
+    "01": Buenos Aires
+"#);
+        let company = config_file(dir.path(), "empresa.toon", r#"formats:
+  ventas:
+    category: Empresa
+    delimiter: ";"
+    fields[1]{nombre,len,tipo,param1,param2}:
+      "Cliente",20,string,"",""
+  sueldos:
+    category: Empresa
+    delimiter: ""
+    fields[1]{nombre,len,tipo,param1,param2}:
+      "Legajo",6,string,"",""
+"#);
+
+        let mut schema = load_config(&system).unwrap();
+        schema.merge(load_config(&company).unwrap());
+
+        let mut names: Vec<&String> = schema.formats.keys().collect();
+        names.sort();
+        assert_eq!(names, ["compras", "sueldos", "ventas"]);
+        assert_eq!(schema.formats["ventas"].category, "Empresa");
+        assert_eq!(schema.formats["ventas"].fields[0].nombre, "Cliente");
+        assert_eq!(schema.format_sources["ventas"], company);
+        assert_eq!(schema.format_sources["compras"], system);
+        assert_eq!(schema.table_sources["provincias"], system);
+        let layers: Vec<&PathBuf> = schema.layers.iter().map(|l| &l.path).collect();
+        assert_eq!(layers, [&system, &company]);
+    }
+
+    #[test]
+    fn config_layer_paths_puts_the_explicit_file_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let explicit = config_file(dir.path(), "empresa.toon", "formats:\n");
+
+        let layers = config_layer_paths(Some(&explicit)).unwrap();
+        let last = layers.last().unwrap();
+        assert_eq!((last.scope, &last.path), ("--config", &explicit));
+
+        assert!(config_layer_paths(Some(&dir.path().join("no-existe.toon"))).is_err());
+    }
+
+    /// Contenido de un archivo de configuración con un único formato.
+    fn format_config(name: &str) -> String {
+        format!(r#"formats:
+  {}:
+    category: Pruebas
+    delimiter: ""
+    fields[1]{{nombre,len,tipo,param1,param2}}:
+      "Nombre",10,string,"",""
+"#, name)
+    }
+
+    #[test]
+    fn load_config_includes_files_directories_and_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        let tables = config_file(dir.path(), "comunes/tablas.toon", "tables:\n  provincias:\n    \"01\": Buenos Aires\n");
+        config_file(dir.path(), "sicore/retenciones.toon", &format_config("retenciones"));
+        config_file(dir.path(), "sicore/notas.txt", "no es configuración");
+        config_file(dir.path(), "arciba/padron.toon", &format_config("padron"));
+        let sifere = config_file(
+            dir.path(), "sifere.toon", &format!("include[1]: comunes/tablas.toon\n{}", format_config("sifere"))
+        );
+        let main = config_file(
+            dir.path(), "parseit.toon", "include[4]: sicore,\"arciba/*.toon\",sifere.toon,comunes/tablas.toon\n"
+        );
+
+        let schema = load_config(&main).unwrap();
+
+        let mut names: Vec<&String> = schema.formats.keys().collect();
+        names.sort();
+        assert_eq!(names, ["padron", "retenciones", "sifere"]);
+        assert_eq!(schema.format_sources["sifere"], sifere);
+        assert_eq!(schema.table_sources["provincias"], tables);
+    }
+
+    #[test]
+    fn load_config_reports_include_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = config_file(dir.path(), "parseit.toon", "include[1]: a.toon\n");
+        config_file(dir.path(), "a.toon", "include[1]: b.toon\n");
+        config_file(dir.path(), "b.toon", "include[1]: a.toon\n");
+
+        let error = load_config(&main).unwrap_err().to_string();
+        assert!(error.starts_with("Inclusión circular"), "{}", error);
+        assert!(error.contains("a.toon → ") && error.ends_with("a.toon"), "{}", error);
+    }
+
+    #[test]
+    fn load_config_reports_definitions_repeated_in_two_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = config_file(dir.path(), "parseit.toon", "include[2]: a.toon,b.toon\n");
+        config_file(dir.path(), "a.toon", &format_config("ventas"));
+        config_file(dir.path(), "b.toon", &format_config("ventas"));
+
+        let error = load_config(&main).unwrap_err().to_string();
+        assert!(error.starts_with("El formato 'ventas' está definido"), "{}", error);
+        assert!(error.contains("a.toon") && error.contains("b.toon"), "{}", error);
+    }
+
+    #[test]
+    fn expand_include_reports_missing_files_and_ignores_unmatched_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("parseit.toon");
+
+        assert!(expand_include(dir.path(), "no-existe.toon", &source).is_err());
+        assert!(expand_include(dir.path(), "regimenes/*.toon", &source).unwrap().is_empty());
+
+        config_file(dir.path(), "regimenes/sicore.toon", "");
+        let files = expand_include(dir.path(), "comunes/../regimenes/*.toon", &source).unwrap();
+        assert_eq!(files, [dir.path().join("regimenes/sicore.toon")]);
+    }
+
+    const INHERITANCE: &str = r#"formats:
+  base:
+    category: Pruebas
+    delimiter: ";"
+    encoding: utf-8
+    date_field: Fecha
+    valid_from: "2024-01-01"
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Fecha",8,date,"%Y%m%d",""
+      "Nombre",10,string,"",""
+  hijo:
+    extends: base
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Nombre",20,string,"",""
+      "Importe",12,zamount,"2",""
+  hijo-ansi:
+    extends: hijo
+    encoding: windows-1252
+    delimiter: "|"
+"#;
+
+    #[test]
+    fn resolve_inherits_the_unset_attributes_and_overrides_fields_by_name() {
+        let schema = schema_from(INHERITANCE);
+        let child = &schema.formats["hijo"];
+
+        assert_eq!((child.category.as_str(), child.delimiter.as_str()), ("Pruebas", ";"));
+        assert_eq!((child.encoding, child.date_field.as_str()), (Encoding::Utf8, "Fecha"));
+        assert_eq!(child.valid_from, "");
+        let fields: Vec<(&str, usize)> = child.fields.iter().map(|f| (f.nombre.as_str(), f.len)).collect();
+        assert_eq!(fields, [("Fecha", 8), ("Nombre", 20), ("Importe", 12)]);
+    }
+
+    #[test]
+    fn resolve_keeps_an_explicit_default_encoding() {
+        let schema = schema_from(INHERITANCE);
+        let grandchild = &schema.formats["hijo-ansi"];
+
+        assert_eq!(grandchild.encoding, Encoding::Windows1252);
+        assert_eq!(grandchild.delimiter, "|");
+        assert_eq!(grandchild.fields.len(), 3);
+    }
+
+    #[test]
+    fn resolve_reports_missing_parents_and_cycles() {
+        let resolve = |content: &str| {
+            let mut schema: ConfigSchema = toon_format::decode_default(content).unwrap();
+            schema.resolve().unwrap_err().to_string()
+        };
+
+        let error = resolve("formats:\n  hijo:\n    extends: base\n");
+        assert_eq!(error, "El formato 'hijo' extiende a 'base', que no existe.");
+
+        let error = resolve("formats:\n  a:\n    extends: b\n  b:\n    extends: a\n");
+        assert_eq!(error, "Herencia circular entre formatos: a → b → a");
+    }
+
+    const GROUPS: &str = r#"field_groups:
+  comprobante[2]{nombre,len,tipo,param1,param2}:
+    "Punto de venta",5,string,"",""
+    "Número",0,group,numero,""
+  numero[1]{nombre,len,tipo,param1,param2}:
+    "Número de Comprobante",20,string,"",""
+  sin-uso[1]{nombre,len,tipo,param1,param2}:
+    "Moneda",3,string,"",""
+formats:
+  ventas:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Fecha",8,date,"%Y%m%d",""
+      "Comprobante",0,group,comprobante,""
+"#;
+
+    #[test]
+    fn resolve_expands_nested_field_groups() {
+        let schema = schema_from(GROUPS);
+
+        let fields: Vec<&str> = schema.formats["ventas"].fields.iter().map(|f| f.nombre.as_str()).collect();
+        assert_eq!(fields, ["Fecha", "Punto de venta", "Número de Comprobante"]);
+        let mut used: Vec<&String> = schema.used_field_groups.iter().collect();
+        used.sort();
+        assert_eq!(used, ["comprobante", "numero"]);
+    }
+
+    #[test]
+    fn resolve_reports_missing_and_circular_field_groups() {
+        let resolve = |content: &str| {
+            let mut schema: ConfigSchema = toon_format::decode_default(content).unwrap();
+            schema.resolve().unwrap_err().to_string()
+        };
+        let format = |group: &str| format!(r#"formats:
+  ventas:
+    fields[1]{{nombre,len,tipo,param1,param2}}:
+      "Grupo",0,group,{},""
+"#, group);
+
+        let error = resolve(&format("otro"));
+        assert_eq!(error, "Formato 'ventas': El grupo de campos 'otro' (campo 'Grupo') no existe.");
+
+        let error = resolve(&format!(r#"field_groups:
+  a[1]{{nombre,len,tipo,param1,param2}}:
+    "B",0,group,b,""
+  b[1]{{nombre,len,tipo,param1,param2}}:
+    "A",0,group,a,""
+{}"#, format("a")));
+        assert_eq!(error, "Formato 'ventas': Los grupos de campos se usan en forma circular: a → b → a");
+    }
+
+    #[test]
+    fn check_config_warns_about_unused_field_groups() {
+        let schema = schema_from(GROUPS);
+        let issues = check_config(&schema);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].rule, issues[0].location.as_str()), ("grupo_sin_uso", "grupo sin-uso"));
+        assert_eq!(issues[0].severity, Severity::Warning);
+    }
+}
@@ -0,0 +1,275 @@
+//! Módulo de codificaciones de caracteres para parseit-rs.
+//! Define las codificaciones admitidas para los archivos de datos (`encoding` de cada
+//! formato o `--encoding`) y la conversión entre sus bytes y el texto de los campos.
+//! Windows-1252 se decodifica con `encoding_rs`; ISO-8859-1 y las páginas EBCDIC de
+//! los extractos de mainframe (CP037 y CP284) se convierten con sus tablas.
+//!
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use encoding_rs::WINDOWS_1252;
+use serde::Deserialize;
+
+/// Codificación de caracteres de un archivo de datos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Encoding {
+    /// Windows-1252, la de los aplicativos del ARCA (por defecto).
+    #[default]
+    Windows1252,
+    /// ISO-8859-1 (Latin-1): cada byte es el carácter Unicode de igual código.
+    Iso8859_1,
+    /// UTF-8.
+    Utf8,
+    /// EBCDIC CP037 (EE.UU./Canadá).
+    Cp037,
+    /// EBCDIC CP284 (España/Latinoamérica).
+    Cp284,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "" | "windows-1252" | "cp1252" => Ok(Encoding::Windows1252),
+            "iso-8859-1" | "latin1" | "latin-1" => Ok(Encoding::Iso8859_1),
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "cp037" | "ibm037" | "ebcdic" => Ok(Encoding::Cp037),
+            "cp284" | "ibm284" => Ok(Encoding::Cp284),
+            _ => Err(format!(
+                "Codificación desconocida: '{}' (use windows-1252, iso-8859-1, utf-8, cp037 o cp284)", name
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Encoding {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Fin de línea de los archivos EBCDIC (NL, `\u{85}`).
+const EBCDIC_NEWLINE: u8 = 0x15;
+
+/// Espacio en EBCDIC.
+const EBCDIC_SPACE: u8 = 0x40;
+
+impl Encoding {
+    /// Nombre de la codificación, tal como se declara en `parseit.toon`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Iso8859_1 => "iso-8859-1",
+            Encoding::Utf8 => "utf-8",
+            Encoding::Cp037 => "cp037",
+            Encoding::Cp284 => "cp284",
+        }
+    }
+
+    /// Indica si la codificación es una página EBCDIC.
+    pub fn is_ebcdic(self) -> bool {
+        matches!(self, Encoding::Cp037 | Encoding::Cp284)
+    }
+
+    /// Byte que separa las líneas del archivo: `\n` o, en EBCDIC, NL (0x15).
+    /// El `\r` (0x0D) que pueda precederlo es el mismo byte en todas las codificaciones.
+    pub fn line_terminator(self) -> u8 {
+        if self.is_ebcdic() { EBCDIC_NEWLINE } else { b'\n' }
+    }
+
+    /// Byte del espacio, con el que se completan los campos de longitud fija.
+    pub fn space(self) -> u8 {
+        if self.is_ebcdic() { EBCDIC_SPACE } else { b' ' }
+    }
+
+    /// Recorta los espacios y saltos de línea finales de una línea, sin decodificarla.
+    ///
+    /// ## Argumentos
+    /// - `bytes`: Bytes crudos de la línea.
+    ///
+    /// ## Retorno
+    /// `&[u8]` - La línea sin los espacios finales (0x40 en EBCDIC).
+    pub fn trim_end(self, bytes: &[u8]) -> &[u8] {
+        if !self.is_ebcdic() {
+            return bytes.trim_ascii_end();
+        }
+
+        let end = bytes.iter()
+            .rposition(|b| !matches!(*b, EBCDIC_SPACE | EBCDIC_NEWLINE | b'\r' | 0x05 | 0x25))
+            .map_or(0, |index| index + 1);
+        &bytes[..end]
+    }
+
+    /// Decodifica los bytes de un campo o de una línea.
+    ///
+    /// ## Argumentos
+    /// - `bytes`: Bytes crudos.
+    ///
+    /// ## Retorno
+    /// `Result<Cow<str>, usize>` - El texto, o la posición (desde 0) del primer byte que
+    /// no es válido en la codificación (sólo puede ocurrir en UTF-8).
+    ///
+    /// ## Ejemplo
+    /// ```
+    /// # use parseit::Encoding;
+    /// let text = Encoding::Cp037.decode(&[0xC8, 0x96, 0x93, 0x81]).unwrap();
+    /// assert_eq!(text, "Hola");
+    /// ```
+    pub fn decode(self, bytes: &[u8]) -> Result<Cow<'_, str>, usize> {
+        match self {
+            // Windows-1252 asigna un carácter a cada uno de los 256 bytes
+            Encoding::Windows1252 => Ok(WINDOWS_1252.decode_without_bom_handling(bytes).0),
+            Encoding::Iso8859_1 => Ok(Cow::Owned(bytes.iter().map(|b| *b as char).collect())),
+            Encoding::Utf8 => std::str::from_utf8(bytes).map(Cow::Borrowed).map_err(|e| e.valid_up_to()),
+            Encoding::Cp037 | Encoding::Cp284 => Ok(Cow::Owned(bytes.iter().map(|b| self.ebcdic_char(*b)).collect())),
+        }
+    }
+
+    /// Decodifica los bytes reemplazando los que no son válidos por `\u{FFFD}`.
+    /// Se usa una vez informado el error de `decode`.
+    pub fn decode_lossy(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes),
+            _ => self.decode(bytes).unwrap_or_default(),
+        }
+    }
+
+    /// Codifica un texto en la codificación.
+    ///
+    /// ## Argumentos
+    /// - `text`: Texto a codificar.
+    ///
+    /// ## Retorno
+    /// `Result<Cow<[u8]>, char>` - Los bytes, o el primer carácter que no se puede
+    /// representar en la codificación.
+    ///
+    /// ## Ejemplo
+    /// ```
+    /// # use parseit::Encoding;
+    /// assert_eq!(Encoding::Cp284.encode("Ñ").unwrap().as_ref(), &[0x7B]);
+    /// assert_eq!(Encoding::Iso8859_1.encode("€"), Err('€'));
+    /// ```
+    pub fn encode(self, text: &str) -> Result<Cow<'_, [u8]>, char> {
+        match self {
+            Encoding::Windows1252 => {
+                let (bytes, _, had_errors) = WINDOWS_1252.encode(text);
+                if !had_errors {
+                    return Ok(bytes);
+                }
+                let mut buffer = [0; 4];
+                Err(text.chars()
+                    .find(|c| WINDOWS_1252.encode(c.encode_utf8(&mut buffer)).2)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER))
+            }
+            Encoding::Iso8859_1 if text.is_ascii() => Ok(Cow::Borrowed(text.as_bytes())),
+            Encoding::Iso8859_1 => text.chars()
+                .map(|c| u8::try_from(c).map_err(|_| c))
+                .collect::<Result<Vec<u8>, char>>()
+                .map(Cow::Owned),
+            Encoding::Utf8 => Ok(Cow::Borrowed(text.as_bytes())),
+            Encoding::Cp037 | Encoding::Cp284 => text.chars()
+                .map(|c| (0..=u8::MAX).find(|b| self.ebcdic_char(*b) == c).ok_or(c))
+                .collect::<Result<Vec<u8>, char>>()
+                .map(Cow::Owned),
+        }
+    }
+
+    /// Carácter de un byte en la página EBCDIC de la codificación.
+    fn ebcdic_char(self, byte: u8) -> char {
+        if self == Encoding::Cp284
+            && let Some((_, c)) = CP284_CHANGES.iter().find(|(b, _)| *b == byte) {
+            return *c;
+        }
+        CP037[byte as usize]
+    }
+}
+
+/// Carácter Unicode de cada byte de la página EBCDIC CP037.
+const CP037: [char; 256] = [
+    '\u{00}', '\u{01}', '\u{02}', '\u{03}', '\u{9C}', '\u{09}', '\u{86}', '\u{7F}', '\u{97}', '\u{8D}', '\u{8E}', '\u{0B}', '\u{0C}', '\u{0D}', '\u{0E}', '\u{0F}',
+    '\u{10}', '\u{11}', '\u{12}', '\u{13}', '\u{9D}', '\u{85}', '\u{08}', '\u{87}', '\u{18}', '\u{19}', '\u{92}', '\u{8F}', '\u{1C}', '\u{1D}', '\u{1E}', '\u{1F}',
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{0A}', '\u{17}', '\u{1B}', '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{05}', '\u{06}', '\u{07}',
+    '\u{90}', '\u{91}', '\u{16}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{04}', '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{14}', '\u{15}', '\u{9E}', '\u{1A}',
+    ' ', '\u{A0}', 'â', 'ä', 'à', 'á', 'ã', 'å', 'ç', 'ñ', '¢', '.', '<', '(', '+', '|',
+    '&', 'é', 'ê', 'ë', 'è', 'í', 'î', 'ï', 'ì', 'ß', '!', '$', '*', ')', ';', '¬',
+    '-', '/', 'Â', 'Ä', 'À', 'Á', 'Ã', 'Å', 'Ç', 'Ñ', '¦', ',', '%', '_', '>', '?',
+    'ø', 'É', 'Ê', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', '`', ':', '#', '@', '\'', '=', '"',
+    'Ø', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', '«', '»', 'ð', 'ý', 'þ', '±',
+    '°', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 'ª', 'º', 'æ', '¸', 'Æ', '¤',
+    'µ', '~', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '¡', '¿', 'Ð', 'Ý', 'Þ', '®',
+    '^', '£', '¥', '·', '©', '§', '¶', '¼', '½', '¾', '[', ']', '¯', '¨', '´', '×',
+    '{', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\u{AD}', 'ô', 'ö', 'ò', 'ó', 'õ',
+    '}', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', '¹', 'û', 'ü', 'ù', 'ú', 'ÿ',
+    '\\', '÷', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '²', 'Ô', 'Ö', 'Ò', 'Ó', 'Õ',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '³', 'Û', 'Ü', 'Ù', 'Ú', '\u{9F}',
+];
+
+/// Bytes en los que la página CP284 difiere de la CP037 (la 'Ñ' y los corchetes,
+/// principalmente).
+const CP284_CHANGES: [(u8, char); 11] = [
+    (0x49, '¦'), (0x4A, '['), (0x5A, ']'), (0x69, '#'), (0x6A, 'ñ'), (0x7B, 'Ñ'),
+    (0xA1, '¨'), (0xB0, '¢'), (0xBA, '^'), (0xBB, '!'), (0xBD, '~'),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_are_parsed_by_name_and_alias() {
+        assert_eq!("".parse::<Encoding>(), Ok(Encoding::Windows1252));
+        assert_eq!("Latin_1".parse::<Encoding>(), Ok(Encoding::Iso8859_1));
+        assert_eq!("UTF8".parse::<Encoding>(), Ok(Encoding::Utf8));
+        assert_eq!("ebcdic".parse::<Encoding>(), Ok(Encoding::Cp037));
+        assert!("utf-16".parse::<Encoding>().is_err());
+
+        for encoding in [Encoding::Windows1252, Encoding::Iso8859_1, Encoding::Utf8, Encoding::Cp037, Encoding::Cp284] {
+            assert_eq!(encoding.name().parse::<Encoding>(), Ok(encoding));
+        }
+    }
+
+    #[test]
+    fn single_byte_encodings_round_trip_every_byte() {
+        for encoding in [Encoding::Iso8859_1, Encoding::Cp037, Encoding::Cp284] {
+            let bytes: Vec<u8> = (0..=u8::MAX).collect();
+            let text = encoding.decode(&bytes).unwrap();
+            assert_eq!(text.chars().count(), 256, "{}", encoding);
+            assert_eq!(encoding.encode(&text).unwrap().as_ref(), bytes.as_slice(), "{}", encoding);
+        }
+    }
+
+    #[test]
+    fn accented_text_uses_the_bytes_of_each_encoding() {
+        assert_eq!(Encoding::Windows1252.encode("Peña €").unwrap().as_ref(), b"Pe\xf1a \x80");
+        assert_eq!(Encoding::Iso8859_1.encode("Peña").unwrap().as_ref(), b"Pe\xf1a");
+        assert_eq!(Encoding::Cp037.encode("ñ").unwrap().as_ref(), &[0x49]);
+        assert_eq!(Encoding::Cp284.encode("ñ").unwrap().as_ref(), &[0x6A]);
+        assert_eq!(Encoding::Windows1252.encode("Ω"), Err('Ω'));
+    }
+
+    #[test]
+    fn utf8_decoding_reports_the_first_invalid_byte() {
+        assert_eq!(Encoding::Utf8.decode(b"Pe\xf1a"), Err(2));
+        assert_eq!(Encoding::Utf8.decode_lossy(b"Pe\xf1a"), "Pe\u{FFFD}a");
+        assert_eq!(Encoding::Windows1252.decode(b"Pe\xf1a").unwrap(), "Peña");
+    }
+
+    #[test]
+    fn trim_end_removes_the_spaces_of_each_encoding() {
+        assert_eq!(Encoding::Windows1252.trim_end(b"abc  \r\n"), b"abc");
+        assert_eq!(Encoding::Cp037.trim_end(&[0xC1, 0x40, 0x40, 0x15]), &[0xC1]);
+        assert_eq!(Encoding::Cp037.trim_end(&[0x40, 0x40]), &[] as &[u8]);
+        assert_eq!(Encoding::Cp037.line_terminator(), 0x15);
+        assert_eq!(Encoding::Utf8.space(), b' ');
+    }
+}
@@ -1,320 +1,1945 @@
 //! Módulo de entrada/salida para parseit-rs.
 //! Proporciona funciones para escribir la salida en diferentes formatos (CSV, terminal interactivo).
-//! También incluye utilidades para leer archivos de datos, como obtener la longitud de la primera línea
-//! de un archivo de longitud fija.
+//! También incluye utilidades para leer archivos de datos (`DataInput`): la entrada estándar,
+//! archivos comprimidos con gzip o zip y la muestra de líneas con la que se deduce el formato.
+//!
+//! Todos los escritores consumen un `RecordStream` a medida que se procesan las líneas,
+//! de modo que el uso de memoria no depende del tamaño del archivo de datos.
 //! 
-use std::{error::Error, fs::File, io::{BufRead, BufReader}};
+use std::{cell::RefCell, error::Error, fs::File, io::{BufRead, BufReader, BufWriter, Seek, SeekFrom}};
+use std::path::{Path, PathBuf};
 
-use encoding_rs::WINDOWS_1252;
 use tempfile::NamedTempFile;
 use csvlens::{run_csvlens_with_options, CsvlensOptions};
-use prettytable::{Cell, Row, Table, format};
+use crate::columnar::{BATCH_ROWS, BatchBuilder};
+use crate::config::{ConfigSchema, FieldDefinition, FormatDefinition};
+use crate::encode::{InputRow, RECORD_TYPE_COLUMN, encode_rows};
+use crate::encoding::Encoding;
+use chrono::Datelike;
+use crate::parse::{DETECTION_SAMPLE_LINES, RecordStream, RenderOptions, Value, validate_file_from};
+use crate::sql::{SqlDialect, SqlOptions, SqlScalar, copy_value, sql_scalar};
+use rusqlite::{Connection, params_from_iter};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use flate2::read::MultiGzDecoder;
+use zip::ZipArchive;
 
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Read, Write};
 
-/// Escribe los registros procesados a la salida estándar en el formato especificado.
+/// Escribe los registros procesados en el formato especificado, a la salida estándar
+/// o al archivo indicado en `output_path` (ver `OutputSink`).
 /// 
 /// ## Argumentos
-/// - `output_typr`: Tipo de salida ("csv" o "term").
-/// - `headers`: Encabezados de las columnas.
-/// - `records`: Registros de datos.
+/// - `output_typr`: Tipo de salida ("csv", "term", "sql", "sqlite", "xlsx", "parquet", "arrow", "json",
+///   "ndjson", "txt" o "html").
+/// - `records`: Flujo de registros procesados.
 /// - `delim_character`: Carácter delimitador para CSV.
+/// - `sql_options`: Opciones de la salida SQL (y de la carga en SQLite).
+/// - `schema`: Esquema de configuración cargado (tablas de lookup de la salida `sqlite`).
+/// - `format_name`: Nombre del formato (nombre de la hoja de la salida `xlsx`).
+/// - `output_path`: Archivo de salida (`None` para la salida estándar). En la salida
+///   `sqlite` es el archivo de la base, si no se indicó `sql_options.database`.
 /// 
 /// ## Retorno
 /// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
 /// 
 /// ## Errores
-/// Retorna un error si falla la escritura en la salida estándar o si el tipo de salida
-/// no es reconocido.
+/// Retorna un error si falla la escritura, si el tipo de salida no es reconocido o si
+/// se indica un archivo de salida para la salida `term`. Ante un error no se crea el
+/// archivo de salida.
 /// 
 /// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_output;
+/// # use parseit::parse::parse_to_records;
+/// # use parseit::sql::{SqlDialect, SqlOptions};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// # let sql_options = SqlOptions { dialect: SqlDialect::Sqlite, table_name: "retenciones".into(), batch_size: 500, copy: false, drop_table: true, database: Some("retenciones.db".into()), append: false };
+/// write_output("csv", records, ",", &sql_options, &schema, "padron", Some(Path::new("padron.csv")))?;
+/// # Ok(())
+/// # }
 /// ```
-/// write_output("csv", headers, records, ",")?;
+pub fn write_output(
+    output_typr: &str,
+    records: RecordStream,
+    delim_character: &str,
+    sql_options: &SqlOptions,
+    schema: &ConfigSchema,
+    format_name: &str,
+    output_path: Option<&Path>,
+    ) -> Result<(), Box<dyn Error>> {
+
+    // Las salidas que no escriben un flujo de bytes no usan `OutputSink`
+    match output_typr {
+        "term" if output_path.is_some() => {
+            return Err("La salida term es interactiva: no admite un archivo de salida.".into());
+        }
+        "term" => return write_interactive(records),
+        "sqlite" => {
+            let mut sql_options = sql_options.clone();
+            if sql_options.database.is_none() {
+                sql_options.database = output_path.map(|path| path.to_string_lossy().to_string());
+            }
+            return write_sqlite_output(records, &sql_options, schema);
+        }
+        _ => {}
+    }
+
+    let mut output = OutputSink::create(output_path)?;
+    match output_typr {
+        "csv" => write_csv_output(records, delim_character, &mut output),
+        "sql" => write_sql_output(records, sql_options, &mut output),
+        "xlsx" => write_xlsx_output(records, format_name, &mut output),
+        "parquet" => write_parquet_output(records, &mut output),
+        "arrow" => write_arrow_output(records, &mut output),
+        "json" => write_json_output(records, true, &mut output),
+        "ndjson" => write_json_output(records, false, &mut output),
+        "txt" => write_txt_output(records, &mut output),
+        "html" => write_html_output(records, &mut output),
+        _ => Err(format!("Tipo de salida desconocido: {}", output_typr).into()),
+    }?;
+    output.commit()
+}
+
+/// Destino de la salida de los escritores: la salida estándar o un archivo.
+///
+/// Los archivos se escriben en un temporal del mismo directorio, que recién toma el
+/// nombre final al confirmar la salida con `commit`. Si la escritura falla, el
+/// temporal se borra al descartar el destino, de modo que nunca queda un archivo a
+/// medio escribir.
+pub enum OutputSink {
+    Stdout(io::Stdout),
+    File { temp: NamedTempFile, path: PathBuf },
+}
+
+impl OutputSink {
+    /// Crea el destino de la salida.
+    ///
+    /// ## Argumentos
+    /// - `path`: Archivo de salida (`None` para la salida estándar).
+    ///
+    /// ## Retorno
+    /// `Result<OutputSink, Box<dyn Error>>` - Destino listo para escribir.
+    ///
+    /// ## Errores
+    /// Retorna un error si la ruta no es un archivo o no se puede crear el temporal
+    /// en su directorio.
+    ///
+    /// ## Ejemplo
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use parseit::{DataInput, load_config_from_paths};
+    /// # use parseit::io::{OutputSink, write_csv_output};
+    /// # use parseit::parse::parse_to_records;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let schema = load_config_from_paths()?;
+    /// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+    /// let mut output = OutputSink::create(Some(Path::new("ventas.csv")))?;
+    /// write_csv_output(records, ",", &mut output)?;
+    /// output.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create(path: Option<&Path>) -> Result<OutputSink, Box<dyn Error>> {
+        let Some(path) = path else {
+            return Ok(OutputSink::Stdout(io::stdout()));
+        };
+
+        let file_name = path.file_name()
+            .ok_or_else(|| format!("La ruta de salida '{}' no es un archivo.", path.display()))?;
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let prefix = format!(".{}.", file_name.to_string_lossy());
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(&prefix).suffix(".tmp");
+        // Los temporales se crean sólo legibles por el usuario; la salida usa los permisos habituales
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            builder.permissions(std::fs::Permissions::from_mode(0o644));
+        }
+        let temp = builder.tempfile_in(directory)
+            .map_err(|e| format!("No se puede crear la salida '{}': {}", path.display(), e))?;
+
+        Ok(OutputSink::File { temp, path: path.to_path_buf() })
+    }
+
+    /// Indica si el destino es una terminal (las salidas binarias no se escriben en ella).
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, OutputSink::Stdout(stdout) if stdout.is_terminal())
+    }
+
+    /// Confirma la salida: vacía la salida estándar o renombra el temporal con el
+    /// nombre final del archivo (reemplazándolo si existe).
+    ///
+    /// ## Errores
+    /// Retorna un error si falla la escritura o el renombrado.
+    pub fn commit(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            OutputSink::Stdout(mut stdout) => stdout.flush()?,
+            OutputSink::File { mut temp, path } => {
+                temp.flush()?;
+                temp.as_file().sync_all()?;
+                temp.persist(&path)
+                    .map_err(|e| format!("No se puede crear la salida '{}': {}", path.display(), e.error))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputSink::Stdout(stdout) => stdout.write(buf),
+            OutputSink::File { temp, .. } => temp.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputSink::Stdout(stdout) => stdout.flush(),
+            OutputSink::File { temp, .. } => temp.flush(),
+        }
+    }
+}
+
+/// Extensión de los archivos de cada tipo de salida, para nombrar las salidas de un
+/// procesamiento por lotes (`None` para la salida `term`, que no genera archivos).
+///
+/// ## Argumentos
+/// - `output_type`: Tipo de salida.
+pub fn output_extension(output_type: &str) -> Option<&'static str> {
+    match output_type {
+        "csv" => Some("csv"),
+        "sql" => Some("sql"),
+        "sqlite" => Some("db"),
+        "xlsx" => Some("xlsx"),
+        "parquet" => Some("parquet"),
+        "arrow" => Some("arrows"),
+        "json" => Some("json"),
+        "ndjson" => Some("ndjson"),
+        "txt" => Some("txt"),
+        "html" => Some("html"),
+        _ => None,
+    }
+}
+
+/// Arma el nombre del archivo de salida de un archivo de datos en un procesamiento por
+/// lotes: el nombre del archivo sin extensión (ni la de compresión) y la extensión del
+/// tipo de salida (ej: `ventas.txt` o `ventas.txt.gz` → `ventas.csv`,
+/// `ventas.2023-01.txt` → `ventas.2023-01.csv`), en el directorio indicado.
+///
+/// ## Argumentos
+/// - `data_file`: Ruta al archivo de datos.
+/// - `output_type`: Tipo de salida.
+/// - `output_dir`: Directorio de las salidas.
+///
+/// ## Retorno
+/// `Result<PathBuf, Box<dyn Error>>` - Ruta del archivo de salida.
+///
+/// ## Errores
+/// Retorna un error si el tipo de salida no genera archivos o si la salida
+/// reemplazaría al propio archivo de datos.
+///
+/// ## Ejemplo
+/// ```
+/// # use std::path::{Path, PathBuf};
+/// # use parseit::io::output_file_name;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = output_file_name("datos/ventas.txt", "csv", Path::new("salidas"))?;
+/// assert_eq!(path, PathBuf::from("salidas/ventas.csv"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn output_file_name(data_file: &str, output_type: &str, output_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let extension = output_extension(output_type)
+        .ok_or_else(|| format!("La salida {} no genera archivos: no admite el procesamiento por lotes.", output_type))?;
+    let name = data_file_name(data_file);
+    let stem = name.file_stem()
+        .ok_or_else(|| format!("No se puede nombrar la salida de '{}'.", data_file))?;
+
+    // No se usa `with_extension`: reemplazaría la última parte con punto del nombre
+    // (ej: `ventas.2023-01.txt` → `ventas.csv`) y dos archivos del lote compartirían la salida
+    let path = output_dir.join(format!("{}.{}", stem.to_string_lossy(), extension));
+
+    let same_file = match (path.canonicalize(), Path::new(data_file).canonicalize()) {
+        (Ok(output), Ok(input)) => output == input,
+        _ => false,
+    };
+    if same_file {
+        return Err(format!(
+            "La salida de '{}' reemplazaría al archivo de datos; indique otro directorio de salida.",
+            data_file
+        ).into());
+    }
+
+    Ok(path)
+}
+
+/// Escribe los registros agrupados en secciones, una por tipo de registro, sin
+/// mantenerlos en memoria.
+///
+/// Con un único tipo de registro las filas se escriben directamente en la salida.
+/// Con varios, las filas de cada tipo se acumulan en un archivo temporal y las
+/// secciones se vuelcan al final, en el orden en que se declararon los tipos
+/// (los tipos sin registros se omiten).
+///
+/// ## Argumentos
+/// - `output`: Destino de la escritura.
+/// - `type_count`: Cantidad de tipos de registro del flujo.
+/// - `records`: Flujo de registros procesados.
+/// - `section_start`: Escribe el comienzo de una sección (recibe el índice del tipo
+///   de registro y el número de sección).
+/// - `write_row`: Escribe una fila (recibe el índice del tipo de registro y los valores tipados).
+/// - `section_end`: Escribe el final de una sección (recibe el índice del tipo de
+///   registro y la cantidad de filas escritas).
+///
+/// ## Errores
+/// Retorna un error si falla la lectura de los registros, la escritura o la
+/// creación de los archivos temporales.
+fn write_sections<W: Write>(
+    output: &mut W,
+    type_count: usize,
+    records: RecordStream,
+    section_start: impl Fn(&mut W, usize, usize) -> io::Result<()>,
+    write_row: impl Fn(&mut dyn Write, usize, &[Value]) -> io::Result<()>,
+    section_end: impl Fn(&mut W, usize, usize) -> io::Result<()>,
+    ) -> Result<(), Box<dyn Error>> {
+
+    if type_count == 1 {
+        section_start(output, 0, 0)?;
+        let mut count = 0;
+        for record in records {
+            write_row(output, 0, &record?.values)?;
+            count += 1;
+        }
+        section_end(output, 0, count)?;
+        return Ok(());
+    }
+
+    // 1. Repartir las filas en un archivo temporal por tipo de registro
+    let mut spools: Vec<Option<BufWriter<File>>> = (0..type_count).map(|_| None).collect();
+    let mut counts = vec![0; type_count];
+
+    for record in records {
+        let record = record?;
+        let spool = match &mut spools[record.record_type] {
+            Some(spool) => spool,
+            spool => spool.insert(BufWriter::new(tempfile::tempfile()?)),
+        };
+        write_row(spool, record.record_type, &record.values)?;
+        counts[record.record_type] += 1;
+    }
+
+    // 2. Volcar cada sección en la salida
+    let mut section_index = 0;
+    for (type_index, spool) in spools.into_iter().enumerate() {
+        let Some(spool) = spool else { continue };
+
+        let mut file = spool.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+
+        section_start(output, type_index, section_index)?;
+        io::copy(&mut file, output)?;
+        section_end(output, type_index, counts[type_index])?;
+        section_index += 1;
+    }
+
+    Ok(())
+}
+
+/// Arma una fila CSV: cada valor entre comillas dobles, con las comillas internas duplicadas.
+///
+/// ## Argumentos
+/// - `values`: Valores de la fila.
+/// - `delim_character`: Carácter delimitador.
+///
+/// ## Retorno
+/// String - Fila lista para escribir (sin salto de línea).
+fn escape_csv_record(values: &[String], delim_character: &str) -> String {
+    values.iter()
+        .map(|v| format!("\"{}\"", v.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(delim_character)
+}
+
+/// Escribe los registros procesados en formato CSV o Long Format.
+/// En los formatos multi-registro cada tipo de registro se escribe en una sección
+/// propia, encabezada por su nombre entre corchetes (ej: `[cabecera]`).
+/// 
+/// ## Argumentos
+/// - `records`: Flujo de registros procesados.
+/// - `delim_character`: Carácter delimitador para CSV.
+/// - `output`: Destino de la salida.
+/// 
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
+/// 
+/// ## Errores
+/// Retorna un error si falla la escritura en la salida.
+/// 
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_csv_output;
+/// # use parseit::parse::parse_to_records;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// # let mut output = parseit::io::OutputSink::create(Some(Path::new("padron.out")))?;
+/// write_csv_output(records, ",", &mut output)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_csv_output(
+    mut records: RecordStream,
+    delim_character: &str,  
+    output: &mut OutputSink,
+    ) -> Result<(), Box<dyn Error>> {
+    
+    let mut output = BufWriter::new(output);
+    let record_types = std::mem::take(&mut records.record_types);
+    let options = records.options;
+
+    write_sections(&mut output, record_types.len(), records,
+        |out, type_index, section_index| {
+            let record_type = &record_types[type_index];
+            if !record_type.name.is_empty() {
+                if section_index > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(out, "[{}]", record_type.name)?;
+            }
+            writeln!(out, "{}", record_type.headers.join(delim_character))
+        },
+        |out, type_index, values| {
+            let values = record_types[type_index].render(values, options);
+            writeln!(out, "{}", escape_csv_record(&values, delim_character))
+        },
+        |_, _, _| Ok(()),
+    )?;
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Escribe los registros procesados en un archivo temporal y abre csvlens para selección interactiva.
+/// En los formatos multi-registro se abre una vista por cada tipo de registro, una
+/// detrás de otra (al salir de una se abre la siguiente).
+/// 
+/// ## Argumentos
+/// - `records`: Flujo de registros procesados.
+/// 
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
+/// 
+/// ## Errores
+/// Retorna un error si falla la creación del archivo temporal, la escritura de datos,
+/// o la ejecución de csvlens.
+/// 
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_interactive;
+/// # use parseit::parse::parse_to_records;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// write_interactive(records)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_interactive(
+    mut records: RecordStream,
+    ) -> Result<(), Box<dyn Error>> {
+    
+    // Usamos '|' como delimitador para la compatibilidad con csvlens
+    const DELIMITER: &str = "|"; 
+
+    let record_types = std::mem::take(&mut records.record_types);
+
+    // 1. Crear un archivo temporal por tipo de registro. Se borran automáticamente cuando salen del scope.
+    // El nombre del tipo de registro se usa como prefijo, así csvlens lo muestra en la barra de estado.
+    let mut temp_files = Vec::new();
+    for record_type in record_types.iter() {
+        let temp_file = if record_type.name.is_empty() {
+            NamedTempFile::new()?
+        } else {
+            tempfile::Builder::new().prefix(&format!("{}-", record_type.name)).tempfile()?
+        };
+        let mut file = BufWriter::new(temp_file.reopen()?);
+
+        // 2. Escribir el encabezado en el archivo temporal
+        writeln!(file, "{}", record_type.headers.join(DELIMITER))?;
+        temp_files.push((temp_file, file, 0));
+    }
+
+    // 3. Escribir los registros en el archivo de su tipo
+    let options = records.options;
+    for record in records {
+        let record = record?;
+        let values = record_types[record.record_type].render(&record.values, options);
+        let (_, file, count) = &mut temp_files[record.record_type];
+        // Escapamos las comillas internas (doble comilla) y envolvemos el valor con comillas
+        writeln!(file, "{}", escape_csv_record(&values, DELIMITER))?;
+        *count += 1;
+    }
+
+    let is_multi_record = record_types.len() > 1;
+
+    for (temp_file, mut file, count) in temp_files {
+        file.flush()?; 
+
+        if is_multi_record && count == 0 {
+            continue;
+        }
+
+        let options = CsvlensOptions {
+            filename: Some(temp_file.path().to_string_lossy().to_string()), 
+            delimiter: Some(DELIMITER.to_string()),
+            ignore_case: true,
+            debug: false, 
+            ..Default::default()
+        };
+        
+        let result = run_csvlens_with_options(options);
+
+        // 4. Manejar la salida (selección o error)
+        match result {
+            Ok(Some(selected_cell)) => {
+                println!("Celda seleccionada por el usuario: {}", selected_cell);
+            }
+            Ok(None) => {
+                // Usuario salió sin seleccionar
+            }
+            Err(e) => {
+                eprintln!("Error al abrir el archivo {}", e);
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+
+/// Flujo de filas de entrada para la codificación.
+type InputRows = Box<dyn Iterator<Item = Result<InputRow, Box<dyn Error>>>>;
+
+/// Convierte un valor JSON en el texto a codificar (`null` es un valor vacío).
+fn json_value_to_string(value: serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s,
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Decodifica un valor de un CSV de entrada e informa, con su número de línea, los bytes
+/// que no son válidos en la codificación.
+fn decode_csv_value(encoding: Encoding, bytes: &[u8], line: u64) -> Result<String, Box<dyn Error>> {
+    encoding.decode(bytes)
+        .map(|text| text.into_owned())
+        .map_err(|offset| format!(
+            "Línea {}: El valor '{}' tiene bytes no válidos en {} (posición {}).",
+            line, encoding.decode_lossy(bytes), encoding, offset + 1
+        ).into())
+}
+
+/// Lee un archivo tabular y devuelve sus filas como mapas columna → valor.
+/// Los archivos `.json`, `.jsonl` y `.ndjson` (también comprimidos, ej: `.json.gz`) se
+/// leen como un arreglo de objetos o como un objeto por línea, siempre en UTF-8; el resto
+/// (y la entrada estándar) se lee como CSV con encabezado, en la codificación indicada.
+/// 
+/// ## Argumentos
+/// - `input`: Archivo de entrada abierto (ver `DataInput`).
+/// - `delim_character`: Delimitador del CSV (se usa su primer carácter).
+/// - `encoding`: Codificación del CSV (la del formato a generar, o la de `--encoding`).
+/// 
+/// ## Retorno
+/// `Result<InputRows, Box<dyn Error>>` - Flujo de filas de entrada.
+/// 
+/// ## Errores
+/// Retorna un error si no se puede leer el encabezado del CSV o si el delimitador no se
+/// puede representar en la codificación. Los errores de lectura, de sintaxis o de
+/// codificación de cada fila se entregan en el flujo.
+///  
+/// ## Ejemplo
+/// ```no_run
+/// # use parseit::{DataInput, Encoding};
+/// # use parseit::io::read_input_rows;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// for row in read_input_rows(DataInput::open("retenciones.csv")?, ",", Encoding::Windows1252)? {
+///     println!("{:?}", row?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn read_input_rows(input: DataInput, delim_character: &str, encoding: Encoding) -> Result<InputRows, Box<dyn Error>> {
+    let extension = data_file_name(input.path())
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if matches!(extension.as_str(), "json" | "jsonl" | "ndjson") {
+        let mut reader = input.into_reader();
+
+        // Un arreglo de objetos se lee completo; los objetos por línea, en flujo
+        let starts_with_array = reader.fill_buf()?.iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|b| *b == b'[');
+        let objects: Box<dyn Iterator<Item = Result<serde_json::Value, serde_json::Error>>> = if starts_with_array {
+            let values: Vec<serde_json::Value> = serde_json::from_reader(reader)?;
+            Box::new(values.into_iter().map(Ok))
+        } else {
+            Box::new(serde_json::Deserializer::from_reader(reader).into_iter::<serde_json::Value>())
+        };
+
+        return Ok(Box::new(objects.map(|object| match object? {
+            serde_json::Value::Object(map) => Ok(map.into_iter()
+                .map(|(key, value)| (key, json_value_to_string(value)))
+                .collect()),
+            other => Err(format!("Se esperaba un objeto JSON por registro y se encontró: {}", other).into()),
+        })));
+    }
+
+    // El delimitador, las comillas y el fin de línea se buscan como bytes de la codificación
+    let encoded_byte = |c: char| -> Result<u8, Box<dyn Error>> {
+        match encoding.encode(c.encode_utf8(&mut [0; 4])).as_deref() {
+            Ok([byte]) => Ok(*byte),
+            _ => Err(format!("El delimitador '{}' no se puede representar con un byte en {}.", c, encoding).into()),
+        }
+    };
+
+    let mut builder = csv::ReaderBuilder::new();
+    builder.delimiter(encoded_byte(delim_character.chars().next().unwrap_or(','))?)
+        .quote(encoded_byte('"')?)
+        .flexible(true);
+    if encoding.is_ebcdic() {
+        builder.terminator(csv::Terminator::Any(encoding.line_terminator()));
+    }
+    let mut reader = builder.from_reader(input.into_reader());
+
+    let headers: Vec<String> = reader.byte_headers()?.iter()
+        .map(|h| decode_csv_value(encoding, h, 1).map(|h| h.trim().to_string()))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Box::new(reader.into_byte_records().map(move |record| {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+        headers.iter().cloned()
+            .zip(record.iter())
+            .map(|(header, value)| Ok((header, decode_csv_value(encoding, value, line)?)))
+            .collect()
+    })))
+}
+
+/// Genera el archivo de longitud fija (o delimitado) del formato a partir de un CSV o JSON
+/// y lo escribe en la salida estándar o en `output_path`. Las líneas se acumulan en un
+/// archivo temporal y sólo se emiten si todas las filas se codificaron sin errores.
+/// 
+/// ## Argumentos
+/// - `input`: Archivo de entrada (CSV o JSON) abierto.
+/// - `format_def`: Definición del formato a generar.
+/// - `schema`: Esquema de configuración cargado.
+/// - `delim_character`: Delimitador del CSV de entrada.
+/// - `output_path`: Archivo de salida (`None` para la salida estándar).
+/// 
+/// ## Retorno
+/// `Result<usize, Box<dyn Error>>` - Cantidad de errores encontrados.
+/// 
+/// ## Errores
+/// Retorna un error si no se puede leer la entrada o falla la escritura en la salida.
+///  
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_encoded_output;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// let format_def = &schema.formats["sifere-retenciones"];
+/// let errors = write_encoded_output(DataInput::open("retenciones.csv")?, format_def, &schema, ",", None)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_encoded_output(
+    input: DataInput,
+    format_def: &FormatDefinition,
+    schema: &ConfigSchema,
+    delim_character: &str,
+    output_path: Option<&Path>,
+    ) -> Result<usize, Box<dyn Error>> {
+
+    let rows = read_input_rows(input, delim_character, format_def.encoding)?;
+
+    let mut spool = BufWriter::new(tempfile::tempfile()?);
+    let error_count = encode_rows(rows, format_def, schema, &mut spool)?;
+    if error_count > 0 {
+        return Ok(error_count);
+    }
+
+    let mut file = spool.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut output = OutputSink::create(output_path)?;
+    io::copy(&mut file, &mut output)?;
+    output.commit()?;
+
+    Ok(0)
+}
+
+/// Ruta con la que se indica la entrada estándar como archivo de datos.
+pub const STDIN_PATH: &str = "-";
+
+/// Primeros bytes de un archivo comprimido con gzip.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Primeros bytes de un archivo zip.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Marca de orden de bytes (BOM) de UTF-8. Algunos proveedores la agregan también a
+/// archivos en ISO-8859-1, así que se descarta sin importar la codificación del formato.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Tope de bytes de la muestra de un archivo de datos, para los archivos sin `\n`
+/// (ej: los extractos EBCDIC, cuyas líneas terminan con NL).
+const DETECTION_SAMPLE_BYTES: u64 = 64 * 1024;
+
+/// Archivo de datos abierto para su lectura, con sus primeras líneas ya leídas.
+///
+/// La ruta `-` es la entrada estándar. Los archivos comprimidos se reconocen por sus
+/// primeros bytes (no por la extensión): los gzip se descomprimen a medida que se leen
+/// y los zip, que deben contener un único archivo, se extraen a un archivo temporal.
+///
+/// Las primeras líneas quedan en memoria como muestra para deducir el formato
+/// (`sample_lines`) y `into_reader` las vuelve a entregar antes del resto, de modo que
+/// cada archivo se abre (y la entrada estándar se lee) una sola vez. Si el archivo
+/// comienza con la marca BOM de UTF-8, se descarta.
+pub struct DataInput {
+    path: String,
+    head: Vec<u8>,
+    rest: Box<dyn BufRead>,
+    // La muestra llegó hasta el final del archivo
+    complete: bool,
+}
+
+impl DataInput {
+    /// Abre el archivo de datos y lee sus primeras `DETECTION_SAMPLE_LINES` líneas no vacías
+    /// (hasta `DETECTION_SAMPLE_BYTES` bytes).
+    ///
+    /// ## Argumentos
+    /// - `file_path`: Ruta al archivo de datos (`-` para la entrada estándar).
+    ///
+    /// ## Retorno
+    /// `Result<DataInput, Box<dyn Error>>` - Archivo listo para leer.
+    ///
+    /// ## Errores
+    /// Retorna un error si no se puede abrir o descomprimir el archivo, o si un zip no
+    /// contiene exactamente un archivo.
+    ///
+    /// ## Ejemplo
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use parseit::{DataInput, load_config_from_paths};
+    /// # use parseit::deduce_format_from;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let schema = load_config_from_paths()?;
+    /// let input = DataInput::open("padron.txt.gz")?;
+    /// let format_name = deduce_format_from(&input, &schema)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open(file_path: &str) -> Result<DataInput, Box<dyn Error>> {
+        DataInput::from_reader(file_path, open_data_reader(file_path)?)
+    }
+
+    /// Igual que `open`, pero a partir de un lector ya abierto (sin descomprimir).
+    ///
+    /// ## Argumentos
+    /// - `file_path`: Nombre con el que se informa el archivo.
+    /// - `rest`: Lector del contenido.
+    pub(crate) fn from_reader(file_path: &str, mut rest: Box<dyn BufRead>) -> Result<DataInput, Box<dyn Error>> {
+        if rest.fill_buf()?.starts_with(UTF8_BOM) {
+            rest.consume(UTF8_BOM.len());
+        }
+
+        let mut head = Vec::new();
+        let mut lines = 0;
+        while lines < DETECTION_SAMPLE_LINES && (head.len() as u64) < DETECTION_SAMPLE_BYTES {
+            let start = head.len();
+            let limit = DETECTION_SAMPLE_BYTES - head.len() as u64;
+            if (&mut rest).take(limit).read_until(b'\n', &mut head)? == 0 {
+                break;
+            }
+            if !head[start..].trim_ascii().is_empty() {
+                lines += 1;
+            }
+        }
+        let complete = rest.fill_buf()?.is_empty();
+
+        Ok(DataInput { path: file_path.to_string(), head, rest, complete })
+    }
+
+    /// Ruta con la que se abrió el archivo de datos.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Primeras líneas no vacías del archivo, sin decodificar y sin el salto de línea final.
+    /// Se usan como muestra para deducir el formato del archivo.
+    ///
+    /// ## Argumentos
+    /// - `encoding`: Codificación con la que se separan las líneas (ver `Encoding::line_terminator`).
+    pub fn sample_lines(&self, encoding: Encoding) -> Vec<Vec<u8>> {
+        let mut lines: Vec<&[u8]> = self.head.split(|b| *b == encoding.line_terminator()).collect();
+        // Si la muestra no llegó al final del archivo, la última línea puede estar incompleta
+        if !self.complete {
+            lines.pop();
+        }
+
+        lines.into_iter()
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .filter(|line| !encoding.trim_end(line).is_empty())
+            .take(DETECTION_SAMPLE_LINES)
+            .map(|line| line.to_vec())
+            .collect()
+    }
+
+    /// Convierte la entrada en un lector del archivo completo, desde la primera línea.
+    pub fn into_reader(self) -> Box<dyn BufRead> {
+        Box::new(io::Read::chain(io::Cursor::new(self.head), self.rest))
+    }
+}
+
+/// Abre el archivo de datos (o la entrada estándar) y, si está comprimido, lo
+/// descomprime según sus primeros bytes.
+///
+/// ## Argumentos
+/// - `file_path`: Ruta al archivo de datos (`-` para la entrada estándar).
+///
+/// ## Retorno
+/// `Result<Box<dyn BufRead>, Box<dyn Error>>` - Lector del contenido descomprimido.
+///
+/// ## Errores
+/// Retorna un error si no se puede abrir el archivo o si un zip no contiene
+/// exactamente un archivo.
+fn open_data_reader(file_path: &str) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let mut reader: Box<dyn BufRead> = if file_path == STDIN_PATH {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(file_path)?))
+    };
+
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(GZIP_MAGIC) {
+        // MultiGzDecoder lee también los archivos con varios miembros gzip concatenados
+        return Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))));
+    }
+    if !magic.starts_with(ZIP_MAGIC) {
+        return Ok(reader);
+    }
+
+    // El índice de un zip está al final del archivo: la entrada estándar se copia a un temporal
+    let archive_file = if file_path == STDIN_PATH {
+        let mut spool = tempfile::tempfile()?;
+        io::copy(&mut reader, &mut spool)?;
+        spool
+    } else {
+        File::open(file_path)?
+    };
+    let mut archive = ZipArchive::new(archive_file)?;
+
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        if entry.is_file() {
+            entries.push((index, entry.name().to_string()));
+        }
+    }
+
+    let [(index, _)] = entries.as_slice() else {
+        let names: Vec<&str> = entries.iter().map(|(_, name)| name.as_str()).collect();
+        return Err(format!(
+            "El archivo zip '{}' contiene {} archivos ({}); se admite un único archivo por zip.",
+            file_path, entries.len(), names.join(", ")
+        ).into());
+    };
+
+    let mut extracted = tempfile::tempfile()?;
+    io::copy(&mut archive.by_index(*index)?, &mut extracted)?;
+    extracted.seek(SeekFrom::Start(0))?;
+
+    Ok(Box::new(BufReader::new(extracted)))
+}
+
+/// Nombre del archivo de datos sin la extensión de compresión (ej: `padron.txt.gz` →
+/// `padron.txt`). La entrada estándar se nombra `stdin`.
+///
+/// ## Argumentos
+/// - `file_path`: Ruta al archivo de datos.
+pub fn data_file_name(file_path: &str) -> PathBuf {
+    if file_path == STDIN_PATH {
+        return PathBuf::from("stdin");
+    }
+
+    let path = Path::new(file_path);
+    match path.extension().map(|e| e.to_string_lossy().to_lowercase()) {
+        Some(extension) if extension == "gz" || extension == "zip" => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Valida el archivo de datos contra el formato y escribe a la salida estándar (o en
+/// `output_path`) un reporte CSV con un error por fila: línea, columna, campo, regla y
+/// valor crudo.
+/// 
+/// ## Argumentos
+/// - `input`: Archivo de datos abierto.
+/// - `format_def`: Definición del formato a validar.
+/// - `schema`: Esquema de configuración cargado.
+/// - `delim_character`: Carácter delimitador para el CSV.
+/// - `output_path`: Archivo del reporte (`None` para la salida estándar).
+/// 
+/// ## Retorno
+/// `Result<usize, Box<dyn Error>>` - Cantidad de errores encontrados.
+/// 
+/// ## Errores
+/// Retorna un error si no se puede leer el archivo o falla la escritura en la salida.
+///  
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_validation_report;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// let format_def = &schema.formats["sifere-retenciones"];
+/// let errors = write_validation_report(DataInput::open("data.dat")?, format_def, &schema, ",", None)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_validation_report(
+    input: DataInput,
+    format_def: &FormatDefinition,
+    schema: &ConfigSchema,
+    delim_character: &str,
+    output_path: Option<&Path>,
+    ) -> Result<usize, Box<dyn Error>> {
+
+    let mut sink = OutputSink::create(output_path)?;
+    let mut output = BufWriter::new(&mut sink);
+
+    writeln!(output, "{}", ["linea", "columna", "campo", "regla", "valor"].join(delim_character))?;
+
+    let error_count = validate_file_from(input, format_def, schema, |error| {
+        let values = [
+            error.line.to_string(),
+            error.offset.to_string(),
+            error.field.clone(),
+            error.rule.to_string(),
+            error.value.clone(),
+        ];
+        writeln!(output, "{}", escape_csv_record(&values, delim_character))?;
+        Ok(())
+    })?;
+
+    output.flush()?;
+    drop(output);
+    sink.commit()?;
+    Ok(error_count)
+}
+
+/// Estadísticas de longitud de las líneas de un archivo de datos.
+/// - lines: Cantidad de líneas no vacías.
+/// - min, max: Longitud mínima y máxima (en bytes, sin espacios ni saltos de línea finales).
+/// - mode: Longitud más frecuente (la menor, si hay empate).
+#[derive(Debug, Default)]
+pub struct LineLengthStats {
+    pub lines: usize,
+    pub min: usize,
+    pub max: usize,
+    pub mode: usize,
+}
+
+/// Recorre el archivo de datos completo y calcula las estadísticas de longitud de sus líneas.
+/// 
+/// ## Argumentos
+/// - `input`: Archivo de datos abierto.
+/// - `encoding`: Codificación con la que se separan las líneas y se recortan los
+///   espacios finales (ver `parse::detection_encoding`).
+/// 
+/// ## Retorno
+/// `Result<LineLengthStats, Box<dyn Error>>` - Estadísticas de longitud o error.
+/// 
+/// ## Errores
+/// Retorna un error si no se puede leer el archivo.
+///  
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::encoding::Encoding;
+/// # use parseit::io::line_length_stats;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let stats = line_length_stats(DataInput::open("data.txt")?, Encoding::Cp037)?;
+/// println!("Longitud más frecuente: {}", stats.mode);
+/// # Ok(())
+/// # }
+/// ```
+pub fn line_length_stats(input: DataInput, encoding: Encoding) -> Result<LineLengthStats, Box<dyn Error>> {
+
+    let reader = input.into_reader();
+    let mut counts: std::collections::BTreeMap<usize, usize> = std::collections::BTreeMap::new();
+
+    for line_result in reader.split(encoding.line_terminator()) {
+        let buffer = line_result?;
+        let length = encoding.trim_end(&buffer).len();
+        if length > 0 {
+            *counts.entry(length).or_default() += 1;
+        }
+    }
+
+    let mut stats = LineLengthStats {
+        lines: counts.values().sum(),
+        min: counts.keys().next().copied().unwrap_or(0),
+        max: counts.keys().next_back().copied().unwrap_or(0),
+        mode: 0,
+    };
+
+    // BTreeMap recorre las longitudes en orden, así en un empate gana la menor
+    let mut mode_count = 0;
+    for (length, count) in counts {
+        if count > mode_count {
+            stats.mode = length;
+            mode_count = count;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Escribe un script SQL, incluyendo la sentencia CREATE TABLE
+/// y la carga de los registros (sentencias INSERT o bloques COPY).
+/// En los formatos multi-registro se crea una tabla por cada tipo de registro,
+/// con el nombre del tipo como sufijo (ej: `retenciones_detalle`).
+///
+/// Los nombres de tabla y de columna se sanean (minúsculas, sin acentos ni símbolos)
+/// y se encierran con las comillas del dialecto. El tipo de cada columna surge de la
+/// definición de su campo y los valores se escriben como literales de ese tipo (ver
+/// `SqlDialect::column_type` y `SqlDialect::literal`), de modo que el script se carga
+/// sin procesamiento posterior.
+/// 
+/// ## Argumentos
+/// - `records`: Flujo de registros procesados. Los encabezados se usan como
+///   nombres de columna SQL y los registros como valores a insertar.
+/// - `sql_options`: Dialecto, nombre de tabla y modo de carga.
+/// - `output`: Destino de la salida.
+/// 
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
+/// 
+/// ## Errores
+/// Retorna un error si falla la escritura en la salida o si se pide `COPY`
+/// con un dialecto distinto de PostgreSQL.
+/// 
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_sql_output;
+/// # use parseit::parse::parse_to_records;
+/// # use parseit::sql::{SqlDialect, SqlOptions};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// # let sql_options = SqlOptions { dialect: SqlDialect::Sqlite, table_name: "retenciones".into(), batch_size: 500, copy: false, drop_table: true, database: Some("retenciones.db".into()), append: false };
+/// # let mut output = parseit::io::OutputSink::create(Some(Path::new("padron.out")))?;
+/// let sql_options = SqlOptions { dialect: SqlDialect::Sqlite, table_name: "retenciones".into(),
+///     batch_size: 500, copy: false, drop_table: true, database: None, append: false };
+/// write_sql_output(records, &sql_options, &mut output)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_sql_output(
+    mut records: RecordStream,
+    sql_options: &SqlOptions,
+    output: &mut OutputSink,
+    ) -> Result<(), Box<dyn Error>> {
+
+    let dialect = sql_options.dialect;
+    if sql_options.copy && dialect != SqlDialect::Postgres {
+        return Err("La carga con COPY sólo está disponible para el dialecto postgres.".into());
+    }
+    
+    // SQL Server admite hasta 1000 filas por sentencia INSERT
+    let mut batch_size = sql_options.batch_size.max(1);
+    if dialect == SqlDialect::SqlServer && batch_size > 1000 {
+        eprintln!("Advertencia: SQL Server admite hasta 1000 filas por INSERT; se usarán lotes de 1000.");
+        batch_size = 1000;
+    }
+
+    let mut output = BufWriter::new(output);
+
+    let record_types = std::mem::take(&mut records.record_types);
+    let options = records.options;
+
+    // Nombre de tabla y nombres de columna de cada tipo de registro
+    let tables: Vec<(String, Vec<String>)> = record_types.iter()
+        .map(|record_type| {
+            let table_name = if record_type.name.is_empty() {
+                sql_options.table_name.clone()
+            } else {
+                format!("{}_{}", sql_options.table_name, record_type.name)
+            };
+            (dialect.table_identifier(&table_name), dialect.column_identifiers(&record_type.headers))
+        })
+        .collect();
+
+    // Filas escritas de cada tabla, para agrupar los INSERT de a `batch_size`
+    let row_counts = RefCell::new(vec![0usize; record_types.len()]);
+
+    write_sections(&mut output, record_types.len(), records,
+        |out, type_index, _| {
+            let (table_name, columns) = &tables[type_index];
+
+            // 1. Sentencia CREATE TABLE
+            writeln!(out, "--------------------------------------------------------")?;
+            writeln!(out, "-- DDL: Creación de tabla {}", table_name)?;
+            writeln!(out, "--------------------------------------------------------")?;
+            if sql_options.drop_table {
+                writeln!(out, "DROP TABLE IF EXISTS {};", table_name)?;
+            }
+            writeln!(out, "CREATE TABLE {} (", table_name)?;
+            
+            let record_type = &record_types[type_index];
+            let column_definitions: Vec<String> = columns.iter()
+                .zip(record_type.fields.iter().zip(record_type.widths.iter()))
+                .map(|(column, (field, width))| format!("    {} {} NULL", column, dialect.column_type(field, *width)))
+                .collect();
+            
+            writeln!(out, "{}", column_definitions.join(",\n"))?;
+            writeln!(out, ");\n")?;
+
+            // 2. Carga de registros
+            writeln!(out, "--------------------------------------------------------")?;
+            writeln!(out, "-- DML: Inserción de registros en {}", table_name)?;
+            writeln!(out, "--------------------------------------------------------")?;
+            if sql_options.copy {
+                writeln!(out, "COPY {} ({}) FROM stdin;", table_name, columns.join(", "))?;
+            }
+            Ok(())
+        },
+        |out, type_index, values| {
+            let (table_name, columns) = &tables[type_index];
+            let fields = &record_types[type_index].fields;
+
+            if sql_options.copy {
+                let copy_values: Vec<String> = values.iter().zip(fields.iter())
+                    .map(|(value, field)| copy_value(value, field, options))
+                    .collect();
+                return writeln!(out, "{}", copy_values.join("\t"));
+            }
+
+            let escaped_values: Vec<String> = values.iter().zip(fields.iter())
+                .map(|(value, field)| dialect.literal(value, field, options))
+                .collect();
+
+            if batch_size == 1 {
+                return writeln!(out, "INSERT INTO {} ({}) VALUES ({});", 
+                    table_name, 
+                    columns.join(", "),
+                    escaped_values.join(", ")
+                );
+            }
+
+            // INSERT de varias filas: cada `batch_size` filas se cierra la sentencia y se abre otra
+            let mut row_counts = row_counts.borrow_mut();
+            let row_index = row_counts[type_index];
+            row_counts[type_index] += 1;
+
+            if row_index.is_multiple_of(batch_size) {
+                if row_index > 0 {
+                    writeln!(out, ";")?;
+                }
+                writeln!(out, "INSERT INTO {} ({}) VALUES", table_name, columns.join(", "))?;
+            } else {
+                writeln!(out, ",")?;
+            }
+            write!(out, "    ({})", escaped_values.join(", "))
+        },
+        |out, _, count| {
+            if sql_options.copy {
+                writeln!(out, "\\.")?;
+            } else if batch_size > 1 && count > 0 {
+                writeln!(out, ";")?;
+            }
+            writeln!(out, "-- {} registros insertados\n", count)
+        },
+    )?;
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Verifica que una tabla existente de la base SQLite tenga las columnas esperadas,
+/// con el mismo nombre, tipo y orden, antes de agregarle registros.
+///
+/// ## Argumentos
+/// - `connection`: Conexión (o transacción) a la base.
+/// - `table_parts`: Nombre de la tabla, con el esquema si se indicó.
+/// - `columns`: Nombre y tipo esperado de cada columna.
+///
+/// ## Retorno
+/// `Result<bool, Box<dyn Error>>` - `true` si la tabla existe y es compatible, `false`
+/// si no existe.
+///
+/// ## Errores
+/// Retorna un error si la tabla existe con otras columnas.
+fn check_sqlite_table(
+    connection: &Connection,
+    table_parts: &[String],
+    columns: &[(String, String)],
+    ) -> Result<bool, Box<dyn Error>> {
+
+    let (table, schema_name) = match table_parts {
+        [schema_name, table] => (table.as_str(), schema_name.as_str()),
+        parts => (parts.last().map_or("", |t| t.as_str()), "main"),
+    };
+
+    let mut statement = connection.prepare("SELECT name, type FROM pragma_table_info(?1, ?2) ORDER BY cid")?;
+    let existing: Vec<(String, String)> = statement
+        .query_map([table, schema_name], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    if existing.is_empty() {
+        return Ok(false);
+    }
+
+    let mut differences = Vec::new();
+    for index in 0..existing.len().max(columns.len()) {
+        match (existing.get(index), columns.get(index)) {
+            (Some((name, sql_type)), Some((expected_name, expected_type))) => {
+                if name != expected_name || !sql_type.eq_ignore_ascii_case(expected_type) {
+                    differences.push(format!(
+                        "columna {}: se esperaba {} {} y existe {} {}",
+                        index + 1, expected_name, expected_type, name, sql_type
+                    ));
+                }
+            }
+            (None, Some((expected_name, expected_type))) => differences.push(format!(
+                "columna {}: falta {} {}", index + 1, expected_name, expected_type
+            )),
+            (Some((name, sql_type)), None) => differences.push(format!(
+                "columna {}: sobra {} {}", index + 1, name, sql_type
+            )),
+            (None, None) => {}
+        }
+    }
+
+    if differences.is_empty() {
+        return Ok(true);
+    }
+
+    Err(format!(
+        "La tabla '{}' no es compatible con el formato ({}). Use otro --table-name o cárguela sin --append para reemplazarla.",
+        table_parts.join("."), differences.join("; ")
+    ).into())
+}
+
+/// Carga los registros directamente en un archivo de base de datos SQLite.
+///
+/// Se crea una tabla por tipo de registro, con las columnas tipadas según la
+/// definición de cada campo (ver `SqlDialect::column_type`). Los campos de tablas de
+/// lookup guardan sólo el código, y cada tabla de lookup usada por el formato se carga
+/// como tabla de referencia `ref_<tabla>` (`codigo`, `descripcion`) para unirla en
+/// las consultas. Toda la carga se hace en una única transacción.
+///
+/// Sin `append`, la tabla se reemplaza (o, con `drop_table` desactivado, se crea y
+/// falla si ya existe). Con `append`, los registros se agregan a la tabla existente
+/// si sus columnas coinciden con las del formato.
+///
+/// ## Argumentos
+/// - `records`: Flujo de registros procesados.
+/// - `sql_options`: Archivo de la base, nombre de tabla y modo de carga.
+/// - `schema`: Esquema de configuración cargado (tablas de lookup).
+///
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
+///
+/// ## Errores
+/// Retorna un error si no se indicó el archivo de la base, si falla la carga o si la
+/// tabla existente no es compatible con el formato. Ante un error no se guarda ningún cambio.
+///
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_sqlite_output;
+/// # use parseit::parse::parse_to_records;
+/// # use parseit::sql::{SqlDialect, SqlOptions};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// # let sql_options = SqlOptions { dialect: SqlDialect::Sqlite, table_name: "retenciones".into(), batch_size: 500, copy: false, drop_table: true, database: Some("retenciones.db".into()), append: false };
+/// write_sqlite_output(records, &sql_options, &schema)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_sqlite_output(
+    mut records: RecordStream,
+    sql_options: &SqlOptions,
+    schema: &ConfigSchema,
+    ) -> Result<(), Box<dyn Error>> {
+
+    let database = sql_options.database.as_deref()
+        .ok_or("La salida sqlite requiere indicar el archivo de la base con --database.")?;
+    let dialect = SqlDialect::Sqlite;
+
+    let mut connection = Connection::open(database)?;
+    let transaction = connection.transaction()?;
+
+    let record_types = std::mem::take(&mut records.record_types);
+    // Se guardan los códigos: las descripciones quedan en las tablas de referencia
+    let options = RenderOptions { dont_use_tables: true, ..records.options };
+
+    // 1. Tablas de referencia con los lookups usados por el formato
+    let mut lookup_names: Vec<&String> = record_types.iter()
+        .flat_map(|record_type| record_type.fields.iter())
+        .filter(|field| field.tipo == "table")
+        .map(|field| &field.param1)
+        .collect();
+    lookup_names.sort();
+    lookup_names.dedup();
+
+    for lookup_name in lookup_names {
+        let Some(lookup) = schema.tables.get(lookup_name) else { continue };
+        let ref_table = dialect.table_identifier(&format!("ref_{}", lookup_name));
+
+        transaction.execute_batch(&format!(
+            "DROP TABLE IF EXISTS {0}; CREATE TABLE {0} (codigo VARCHAR PRIMARY KEY, descripcion VARCHAR);",
+            ref_table
+        ))?;
+        let mut insert = transaction.prepare(&format!("INSERT INTO {} (codigo, descripcion) VALUES (?1, ?2)", ref_table))?;
+        for (code, description) in lookup {
+            insert.execute([code, description])?;
+        }
+    }
+
+    // 2. Una tabla de datos por tipo de registro
+    let mut inserts = Vec::new();
+    let mut table_names = Vec::new();
+    for record_type in record_types.iter() {
+        let table_name = if record_type.name.is_empty() {
+            sql_options.table_name.clone()
+        } else {
+            format!("{}_{}", sql_options.table_name, record_type.name)
+        };
+        let table_parts = dialect.table_name_parts(&table_name);
+        let table = dialect.table_identifier(&table_name);
+
+        let columns: Vec<(String, String)> = dialect.column_names(&record_type.headers).into_iter()
+            .zip(record_type.fields.iter())
+            .map(|(name, field)| (name, dialect.column_type(field, field.len)))
+            .collect();
+
+        let exists = sql_options.append && check_sqlite_table(&transaction, &table_parts, &columns)?;
+        if !exists {
+            if sql_options.drop_table {
+                transaction.execute_batch(&format!("DROP TABLE IF EXISTS {};", table))?;
+            }
+            let column_definitions: Vec<String> = columns.iter()
+                .map(|(name, sql_type)| format!("{} {} NULL", dialect.quote_identifier(name), sql_type))
+                .collect();
+            transaction.execute_batch(&format!("CREATE TABLE {} ({});", table, column_definitions.join(", ")))?;
+        }
+
+        let column_list: Vec<String> = columns.iter().map(|(name, _)| dialect.quote_identifier(name)).collect();
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+        inserts.push(transaction.prepare(&format!(
+            "INSERT INTO {} ({}) VALUES ({})", table, column_list.join(", "), placeholders.join(", ")
+        ))?);
+        table_names.push(table_parts.join("."));
+    }
+
+    // 3. Insertar los registros a medida que se leen
+    let mut counts = vec![0; record_types.len()];
+    for record in records {
+        let record = record?;
+        let fields = &record_types[record.record_type].fields;
+
+        // Las líneas cortas completan las columnas faltantes con NULL
+        let values: Vec<SqlScalar> = fields.iter().enumerate()
+            .map(|(index, field)| match record.values.get(index) {
+                Some(value) => sql_scalar(value, field, options),
+                None => SqlScalar::Null,
+            })
+            .collect();
+
+        inserts[record.record_type].execute(params_from_iter(values.iter()))?;
+        counts[record.record_type] += 1;
+    }
+
+    drop(inserts);
+    transaction.commit()?;
+
+    for (table_name, count) in table_names.iter().zip(counts.iter()) {
+        eprintln!("Se cargaron {} registros en la tabla '{}' de '{}'.", count, table_name, database);
+    }
+
+    Ok(())
+}
+
+/// Convierte un valor tipado en su representación JSON.
+///
+/// ## Argumentos
+/// - `value`: Valor del campo.
+/// - `dont_use_tables`: Indica si se omite la descripción de las tablas de lookup.
+///
+/// ## Retorno
+/// String - Los montos como números (con todos sus decimales), las fechas como texto
+/// `AAAA-MM-DD` (`null` si no se informaron) y los códigos de tablas como un objeto
+/// `{"code": ..., "description": ...}` (descripción `null` si no figura en la tabla).
+fn json_value(value: &Value, dont_use_tables: bool) -> String {
+    match value {
+        Value::Text(text) => serde_json::Value::from(text.as_str()).to_string(),
+        // El Display de Decimal nunca usa notación exponencial: es un número JSON válido
+        Value::Decimal(number) => number.to_string(),
+        Value::Date(Some(date)) => format!("\"{}\"", date.format("%Y-%m-%d")),
+        Value::Date(None) => "null".to_string(),
+        Value::Coded { code, description } => {
+            let description = description.as_deref().filter(|_| !dont_use_tables);
+            serde_json::json!({ "code": code, "description": description }).to_string()
+        }
+    }
+}
+
+/// Calcula las claves JSON de los campos de un tipo de registro: el nombre del campo,
+/// con un sufijo `_2`, `_3`... si se repite, para que ninguna clave se pise.
+///
+/// ## Argumentos
+/// - `fields`: Campos del tipo de registro.
+/// - `with_record_type`: Indica si los objetos llevan además la clave `record_type`.
+///
+/// ## Retorno
+/// `Vec<String>` - Una clave (ya escapada como texto JSON) por campo.
+fn json_keys(fields: &[FieldDefinition], with_record_type: bool) -> Vec<String> {
+    let mut used: Vec<String> = Vec::new();
+    if with_record_type {
+        used.push(RECORD_TYPE_COLUMN.to_string());
+    }
+
+    fields.iter()
+        .map(|field| {
+            let mut key = field.nombre.clone();
+            let mut suffix = 2;
+            while used.contains(&key) {
+                key = format!("{}_{}", field.nombre, suffix);
+                suffix += 1;
+            }
+            used.push(key.clone());
+            serde_json::Value::from(key).to_string()
+        })
+        .collect()
+}
+
+/// Escribe los registros procesados en JSON, con un objeto por
+/// registro cuyas claves son los nombres de los campos (ver `json_value` para el tipo
+/// de cada valor). Los registros se escriben a medida que se procesan, en el orden
+/// del archivo; en los formatos multi-registro cada objeto lleva además la clave
+/// `record_type` con el nombre de su tipo de registro.
+///
+/// ## Argumentos
+/// - `records`: Flujo de registros procesados.
+/// - `as_array`: `true` para un único arreglo JSON, `false` para un objeto por línea (NDJSON).
+/// - `output`: Destino de la salida.
+///
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
+///
+/// ## Errores
+/// Retorna un error si falla la lectura del archivo o la escritura en la salida.
+///
+/// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_json_output;
+/// # use parseit::parse::parse_to_records;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// # let mut output = parseit::io::OutputSink::create(Some(Path::new("padron.out")))?;
+/// write_json_output(records, false, &mut output)?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn write_output(
-    output_typr: &str,
-    headers: Vec<String>,
-    records: Vec<Vec<String>>,
-    delim_character: &str,
+pub fn write_json_output(
+    mut records: RecordStream,
+    as_array: bool,
+    output: &mut OutputSink,
     ) -> Result<(), Box<dyn Error>> {
-    match output_typr {
-        "csv" => write_csv_output(headers, records, delim_character),
-        "term" => write_interactive(headers, records),
-        "sql" => write_sql_output(headers, records),
-        "txt" => write_txt_output(headers, records),
-        "html" => write_html_output(headers, records),
-        _ => Err(format!("Tipo de salida desconocido: {}", output_typr).into()),
+
+    let mut output = BufWriter::new(output);
+    let record_types = std::mem::take(&mut records.record_types);
+    let dont_use_tables = records.options.dont_use_tables;
+
+    let keys: Vec<Vec<String>> = record_types.iter()
+        .map(|record_type| json_keys(&record_type.fields, !record_type.name.is_empty()))
+        .collect();
+
+    if as_array {
+        write!(output, "[")?;
+    }
+
+    for (index, record) in records.enumerate() {
+        let record = record?;
+        let record_type = &record_types[record.record_type];
+
+        let mut members = Vec::with_capacity(record.values.len() + 1);
+        if !record_type.name.is_empty() {
+            members.push(format!("\"{}\":{}", RECORD_TYPE_COLUMN, serde_json::Value::from(record_type.name.as_str())));
+        }
+        members.extend(keys[record.record_type].iter().zip(record.values.iter())
+            .map(|(key, value)| format!("{}:{}", key, json_value(value, dont_use_tables))));
+
+        let object = format!("{{{}}}", members.join(","));
+        if as_array {
+            write!(output, "{}\n{}", if index == 0 { "" } else { "," }, object)?;
+        } else {
+            writeln!(output, "{}", object)?;
+        }
     }
+
+    if as_array {
+        writeln!(output, "\n]")?;
+    }
+
+    output.flush()?;
+    Ok(())
 }
 
-/// Escribe los registros procesados a la salida estándar en formato CSV o Long Format.
-/// 
+/// Verifica que el destino no sea una terminal antes de escribir una salida binaria.
+///
 /// ## Argumentos
-/// - `headers`: Encabezados de las columnas.
-/// - `records`: Registros de datos.
-/// - `delim_character`: Carácter delimitador para CSV.
-/// 
+/// - `output`: Destino de la salida.
+/// - `output_type`: Tipo de salida, para el mensaje de error.
+///
+/// ## Errores
+/// Retorna un error si el destino es una terminal.
+fn check_binary_output(output: &OutputSink, output_type: &str) -> Result<(), Box<dyn Error>> {
+    if output.is_terminal() {
+        return Err(format!(
+            "La salida {0} es binaria: use --output o redirija la salida a un archivo (ej: > salida.{0}).",
+            output_type
+        ).into());
+    }
+    Ok(())
+}
+
+/// Recorre los registros y los entrega en lotes Arrow de hasta `BATCH_ROWS` registros,
+/// de modo que sólo un lote se mantiene en memoria.
+///
+/// ## Argumentos
+/// - `records`: Flujo de registros procesados.
+/// - `start`: Recibe el esquema Arrow antes del primer lote (para crear el escritor).
+/// - `write_batch`: Escribe cada lote.
+///
+/// ## Errores
+/// Retorna un error si falla la lectura de los registros o la escritura de un lote.
+fn write_batches<W>(
+    mut records: RecordStream,
+    start: impl FnOnce(arrow::datatypes::SchemaRef) -> Result<W, Box<dyn Error>>,
+    mut write_batch: impl FnMut(&mut W, &RecordBatch) -> Result<(), Box<dyn Error>>,
+    ) -> Result<W, Box<dyn Error>> {
+
+    let record_types = std::mem::take(&mut records.record_types);
+    let options = records.options;
+
+    let mut batches = BatchBuilder::new(&record_types);
+    let mut writer = start(batches.schema())?;
+
+    for record in records {
+        batches.append(&record?, &record_types, options);
+        if batches.len() >= BATCH_ROWS {
+            write_batch(&mut writer, &batches.finish()?)?;
+        }
+    }
+    if !batches.is_empty() {
+        write_batch(&mut writer, &batches.finish()?)?;
+    }
+
+    Ok(writer)
+}
+
+/// Escribe los registros procesados como un archivo Parquet,
+/// con el esquema derivado del formato (ver `columnar`) y compresión Snappy.
+///
+/// Los registros se escriben en grupos de filas de `BATCH_ROWS` registros a medida
+/// que se procesan, por lo que el uso de memoria no depende del tamaño del archivo.
+/// En los formatos multi-registro se escribe una única tabla con la columna
+/// `record_type` y las columnas de todos los tipos.
+///
+/// ## Argumentos
+/// - `records`: Flujo de registros procesados.
+/// - `output`: Destino de la salida.
+///
 /// ## Retorno
 /// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
-/// 
+///
 /// ## Errores
-/// Retorna un error si falla la escritura en la salida estándar.
-/// 
+/// Retorna un error si el destino es una terminal o si falla la escritura.
+///
 /// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_parquet_output;
+/// # use parseit::parse::parse_to_records;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// # let mut output = parseit::io::OutputSink::create(Some(Path::new("padron.out")))?;
+/// write_parquet_output(records, &mut output)?;
+/// # Ok(())
+/// # }
 /// ```
-/// write_csv_output(headers, records, ",")?;
-/// ```
-pub fn write_csv_output(
-    headers: Vec<String>,
-    records: Vec<Vec<String>>,
-    delim_character: &str,  
-    ) -> Result<(), Box<dyn Error>> {
-    
-    let mut output = io::stdout().lock();
-    
-    writeln!(output, "{}", headers.join(delim_character))?;
-    
-    for record in records.iter() {
+pub fn write_parquet_output(records: RecordStream, output: &mut OutputSink) -> Result<(), Box<dyn Error>> {
+    check_binary_output(output, "parquet")?;
 
-        let escaped_record: Vec<String> = record.iter()
-            .map(|v| format!("\"{}\"", v.replace('"', "\"\"")))
-            .collect();
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(BATCH_ROWS)
+        .build();
 
-        writeln!(output, "{}", escaped_record.join(delim_character))?;
-    }
+    let writer = write_batches(records,
+        |schema| Ok(ArrowWriter::try_new(BufWriter::new(output), schema, Some(properties))?),
+        |writer, batch| {
+            writer.write(batch)?;
+            // Cada lote se cierra como un grupo de filas para no acumularlos en memoria
+            writer.flush()?;
+            Ok(())
+        },
+    )?;
 
+    writer.into_inner()?.flush()?;
     Ok(())
 }
 
-/// Escribe los registros procesados en un archivo temporal y abre csvlens para selección interactiva.
-/// 
+/// Escribe los registros procesados en el formato de flujo de
+/// Arrow IPC (`.arrows`), con el esquema derivado del formato (ver `columnar`).
+///
+/// Se usa el formato de flujo (y no el de archivo) porque admite que el diccionario
+/// de las columnas `table` cambie entre lotes, de modo que los registros se escriben
+/// en lotes de `BATCH_ROWS` a medida que se procesan.
+///
 /// ## Argumentos
-/// - `headers`: Encabezados de las columnas.
-/// - `records`: Registros de datos.
-/// 
+/// - `records`: Flujo de registros procesados.
+/// - `output`: Destino de la salida.
+///
 /// ## Retorno
 /// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
-/// 
+///
 /// ## Errores
-/// Retorna un error si falla la creación del archivo temporal, la escritura de datos,
-/// o la ejecución de csvlens.
-/// 
+/// Retorna un error si el destino es una terminal o si falla la escritura.
+///
 /// ## Ejemplo
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_arrow_output;
+/// # use parseit::parse::parse_to_records;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// # let mut output = parseit::io::OutputSink::create(Some(Path::new("padron.out")))?;
+/// write_arrow_output(records, &mut output)?;
+/// # Ok(())
+/// # }
 /// ```
-/// write_interactive(headers, records)?;
-/// ```
-pub fn write_interactive(
-    headers: Vec<String>,
-    records: Vec<Vec<String>>,
-    ) -> Result<(), Box<dyn Error>> {
-    
-    // 1. Crear un archivo temporal. Se borra automáticamente cuando 'temp_file' sale del scope.
-    let temp_file = NamedTempFile::new()?;
-    let file_path = temp_file.path().to_string_lossy().to_string();
-    let mut file = temp_file.reopen()?; 
-    
-    // Usamos '|' como delimitador para la compatibilidad con csvlens
-    const DELIMITER: &str = "|"; 
-    
-    // 2. Escribir Encabezado y Registros en el archivo temporal
-    writeln!(file, "{}", headers.join(DELIMITER))?;
-    
-    for record in records.iter() {
-        // Escapamos las comillas internas (doble comilla) y envolvemos el valor con comillas
-        let escaped_record: Vec<String> = record.iter()
-            .map(|v| format!("\"{}\"", v.replace('"', "\"\"")))
-            .collect();
-            
-        writeln!(file, "{}", escaped_record.join(DELIMITER))?;
-    }
-    
-    file.flush()?; 
-    
-    let options = CsvlensOptions {
-        filename: Some(file_path), 
-        delimiter: Some(DELIMITER.to_string()),
-        ignore_case: true,
-        debug: false, 
-        ..Default::default()
-    };
-    
-    let result = run_csvlens_with_options(options);
+pub fn write_arrow_output(records: RecordStream, output: &mut OutputSink) -> Result<(), Box<dyn Error>> {
+    check_binary_output(output, "arrow")?;
 
-    // 4. Manejar la salida (selección o error)
-    match result {
-        Ok(Some(selected_cell)) => {
-            println!("Celda seleccionada por el usuario: {}", selected_cell);
-        }
-        Ok(None) => {
-            // Usuario salió sin seleccionar
-        }
-        Err(e) => {
-            eprintln!("Error al abrir el archivo {}", e);
-        }
-    }
-    
+    let mut writer = write_batches(records,
+        |schema| Ok(StreamWriter::try_new(BufWriter::new(output), &schema)?),
+        |writer, batch| Ok(writer.write(batch)?),
+    )?;
+
+    writer.finish()?;
+    writer.into_inner()?.flush()?;
     Ok(())
 }
 
+/// Cantidad máxima de filas de una hoja de Excel (incluido el encabezado).
+const XLSX_MAX_ROWS: u32 = 1_048_576;
 
-/// Lee la primera línea del archivo de datos y devuelve su longitud.
-/// 
+/// Ancho máximo, en caracteres, de las columnas de la salida `xlsx`.
+const XLSX_MAX_COLUMN_WIDTH: usize = 80;
+
+/// Arma un nombre de hoja válido para Excel: sin los caracteres `[]:*?/\` y con
+/// hasta 31 caracteres.
+///
 /// ## Argumentos
-/// - `file_path`: Ruta al archivo de datos.
-/// 
+/// - `name`: Nombre deseado (formato o tipo de registro).
+///
 /// ## Retorno
-/// `Result<usize, Box<dyn Error>>` - Longitud de la primera línea o error.
-/// 
-/// ## Errores
-/// Retorna un error si no se puede abrir o leer el archivo.
-///  
-/// ## Ejemplo
-/// ```
-/// let length = get_first_line_length("data.txt")?;
-/// println!("La longitud de la primera línea es: {}", length);
-/// ```
-pub fn get_first_line_length(file_path: &str) -> Result<usize, Box<dyn Error>> {
+/// String - Nombre de la hoja ("Hoja1" si queda vacío).
+fn xlsx_sheet_name(name: &str) -> String {
+    let sheet_name: String = name.chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+        .take(31)
+        .collect();
+    let sheet_name = sheet_name.trim_matches('\'').to_string();
 
-    let file = File::open(file_path)?;
-    
-    let mut reader = BufReader::new(file);
-    let mut buffer = Vec::new();
-    reader.read_until(b'\n', &mut buffer)?;
-    
-    let (cow, _, _) = WINDOWS_1252.decode(&buffer);
-    let line = cow.to_string(); 
-    Ok(line.trim_end().len()) 
+    if sheet_name.is_empty() { "Hoja1".to_string() } else { sheet_name }
 }
-/// Escribe un script SQL a la salida estándar, incluyendo la sentencia CREATE TABLE
-/// y las sentencias INSERT correspondientes a los registros.
-/// 
+
+/// Escribe los registros procesados como un libro de Excel (.xlsx).
+///
+/// Cada tipo de registro se escribe en una hoja propia (en los formatos de un solo tipo,
+/// una hoja con el nombre del formato), con la fila de encabezados inmovilizada, el
+/// autofiltro activado y el ancho de las columnas calculado a partir de `FieldDefinition.len`.
+/// Las celdas conservan el tipo del valor:
+/// - Montos: celdas numéricas con separador de miles y los decimales del campo.
+/// - Fechas: celdas de fecha (`dd/mm/yyyy`).
+/// - Textos y códigos de tablas: celdas de texto, por lo que los CUIT y números de
+///   documento conservan los ceros a la izquierda.
+///
+/// Las hojas se escriben en modo de memoria constante: las filas se vuelcan a archivos
+/// temporales a medida que se procesan, y el libro se escribe en `output` sin cargarlo
+/// completo en memoria.
+///
 /// ## Argumentos
-/// - `headers`: Encabezados de las columnas (usados como nombres de columna SQL).
-/// - `records`: Registros de datos (usados como valores a insertar).
-/// 
+/// - `records`: Flujo de registros procesados.
+/// - `format_name`: Nombre del formato, para la hoja de los formatos de un solo tipo.
+/// - `output`: Destino de la salida.
+///
 /// ## Retorno
 /// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
-/// 
+///
 /// ## Errores
-/// Retorna un error si falla la escritura en la salida estándar.
-/// 
+/// Retorna un error si el destino es una terminal, si una hoja supera la
+/// cantidad máxima de filas de Excel o si falla la generación del libro.
+///
 /// ## Ejemplo
-/// ```ignore
-/// // La tabla se llamará 'processed_data' por defecto.
-/// write_sql_output(headers, records)?;
+/// ```no_run
+/// # use std::path::Path;
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::io::write_xlsx_output;
+/// # use parseit::parse::parse_to_records;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let schema = load_config_from_paths()?;
+/// # let records = parse_to_records(DataInput::open("padron.txt")?, &schema.formats["sifere-retenciones"], &schema, false, false, false);
+/// # let mut output = parseit::io::OutputSink::create(Some(Path::new("padron.out")))?;
+/// write_xlsx_output(records, "padron", &mut output)?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn write_sql_output(
-    headers: Vec<String>,
-    records: Vec<Vec<String>>,
+pub fn write_xlsx_output(
+    mut records: RecordStream,
+    format_name: &str,
+    output: &mut OutputSink,
     ) -> Result<(), Box<dyn Error>> {
-    
-    let mut output = io::stdout().lock();
-    const TABLE_NAME: &str = "processed_data";
-    
-    // Función auxiliar para limpiar nombres de columna (reemplazar caracteres especiales)
-    let clean_headers: Vec<String> = headers.iter()
-        .map(|h| h.replace(' ', "_").to_uppercase())
+
+    check_binary_output(output, "xlsx")?;
+
+    let record_types = std::mem::take(&mut records.record_types);
+    let options = records.options;
+
+    let header_format = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("dd/mm/yyyy");
+    let text_format = Format::new().set_num_format("@");
+
+    let mut workbook = Workbook::new();
+    let mut sheet_names = Vec::with_capacity(record_types.len());
+
+    // 1. Una hoja por tipo de registro, con encabezados, anchos e inmovilización
+    for record_type in record_types.iter() {
+        let name = if record_type.name.is_empty() { format_name } else { &record_type.name };
+        let mut sheet_name = xlsx_sheet_name(name);
+        // Los nombres de hoja no distinguen mayúsculas y deben ser únicos
+        let mut suffix = 2;
+        while sheet_names.iter().any(|n: &String| n.eq_ignore_ascii_case(&sheet_name)) {
+            let base: String = xlsx_sheet_name(name).chars().take(27).collect();
+            sheet_name = format!("{} ({})", base, suffix);
+            suffix += 1;
+        }
+
+        let worksheet = workbook.add_worksheet_with_constant_memory();
+        worksheet.set_name(&sheet_name)?;
+
+        for (col, (header, width)) in record_type.headers.iter().zip(record_type.widths.iter()).enumerate() {
+            let col = col as u16;
+            worksheet.set_column_width(col, (width + 2).min(XLSX_MAX_COLUMN_WIDTH) as f64)?;
+            worksheet.write_string_with_format(0, col, header, &header_format)?;
+        }
+        worksheet.set_freeze_panes(1, 0)?;
+
+        sheet_names.push(sheet_name);
+    }
+
+    // 2. Las filas se escriben en la hoja de su tipo, en el orden del archivo
+    let mut next_rows = vec![1u32; record_types.len()];
+    let mut amount_formats: Vec<Vec<Option<Format>>> = record_types.iter()
+        .map(|record_type| vec![None; record_type.fields.len()])
         .collect();
 
-    // 1. Sentencia CREATE TABLE
-    writeln!(output, "--------------------------------------------------------")?;
-    writeln!(output, "-- DDL: Creación de tabla '{}'", TABLE_NAME)?;
-    writeln!(output, "--------------------------------------------------------")?;
-    writeln!(output, "DROP TABLE IF EXISTS {};", TABLE_NAME)?;
-    write!(output, "CREATE TABLE {} (\n", TABLE_NAME)?;
-    
-    let mut column_definitions = Vec::new();
-    // Asumimos que todos los campos serán VARCHAR o TEXT para simplificar y asegurar la compatibilidad.
-    for (i, header) in clean_headers.iter().enumerate() {
-        let definition = if i < clean_headers.len() - 1 {
-            format!("    {} VARCHAR(255) NULL,", header)
-        } else {
-            format!("    {} VARCHAR(255) NULL", header) // El último no lleva coma
-        };
-        column_definitions.push(definition);
+    for record in records {
+        let record = record?;
+        let record_type = &record_types[record.record_type];
+        let row = next_rows[record.record_type];
+        if row >= XLSX_MAX_ROWS {
+            return Err(format!(
+                "La hoja '{}' supera la cantidad máxima de filas de Excel ({}).",
+                sheet_names[record.record_type], XLSX_MAX_ROWS
+            ).into());
+        }
+
+        let worksheet = workbook.worksheet_from_index(record.record_type)?;
+        for (col_index, (value, field)) in record.values.iter().zip(record_type.fields.iter()).enumerate() {
+            let col = col_index as u16;
+            match value {
+                Value::Decimal(number) => {
+                    // El formato numérico se arma con la escala del monto y se reutiliza por columna
+                    let number_format = amount_formats[record.record_type][col_index].get_or_insert_with(|| {
+                        let scale = number.scale() as usize;
+                        let decimals = if scale == 0 { String::new() } else { format!(".{}", "0".repeat(scale)) };
+                        Format::new().set_num_format(format!("#,##0{}", decimals))
+                    });
+                    let number = number.to_string().parse::<f64>()?;
+                    worksheet.write_number_with_format(row, col, number, number_format)?;
+                }
+                Value::Date(Some(date)) => {
+                    let excel_date = ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)?;
+                    worksheet.write_datetime_with_format(row, col, &excel_date, &date_format)?;
+                }
+                Value::Date(None) => {}
+                _ => {
+                    let text = value.render(field, options.format_numeric, options.dont_use_tables);
+                    worksheet.write_string_with_format(row, col, &text, &text_format)?;
+                }
+            }
+        }
+        next_rows[record.record_type] = row + 1;
     }
-    
-    writeln!(output, "{}", column_definitions.join("\n"))?;
-    writeln!(output, ");\n")?;
-
-    // 2. Sentencias INSERT
-    writeln!(output, "--------------------------------------------------------")?;
-    writeln!(output, "-- DML: Inserción de {} registros", records.len())?;
-    writeln!(output, "--------------------------------------------------------")?;
-
-    for record in records.iter() {
-        // Escapamos las comillas internas (doble comilla) y envolvemos el valor con comillas simples para SQL
-        let escaped_values: Vec<String> = record.iter()
-            .map(|v| {
-                // Reemplazamos ' con '' (escape estándar SQL) y envolvemos en comillas simples
-                format!("'{}'", v.replace('\'', "''"))
-            })
-            .collect();
 
-        writeln!(output, "INSERT INTO {} ({}) VALUES ({});", 
-            TABLE_NAME, 
-            clean_headers.join(", "),
-            escaped_values.join(", ")
-        )?;
+    // 3. Autofiltro sobre el rango escrito de cada hoja
+    for (index, record_type) in record_types.iter().enumerate() {
+        let last_col = record_type.headers.len().saturating_sub(1) as u16;
+        let worksheet = workbook.worksheet_from_index(index)?;
+        worksheet.autofilter(0, 0, next_rows[index] - 1, last_col)?;
     }
 
+    // El libro se comprime directamente en la salida, sin armarlo antes en memoria
+    let mut output = BufWriter::new(output);
+    workbook.save_to_writer(&mut output)?;
+    output.flush()?;
     Ok(())
 }
 
-/// Genera y escribe en stdout una tabla de texto formateada usando prettytable-rs.
+/// Arma una línea separadora de la tabla de texto (ej: `+-----+-----+`).
+///
+/// ## Argumentos
+/// - `widths`: Ancho de cada columna.
+/// - `fill`: Carácter de relleno ('-' o '=').
+fn txt_separator(widths: &[usize], fill: char) -> String {
+    let cells: Vec<String> = widths.iter()
+        .map(|w| fill.to_string().repeat(w + 2))
+        .collect();
+    format!("+{}+", cells.join("+"))
+}
+
+/// Arma una fila de la tabla de texto, con cada valor alineado a la izquierda.
+///
+/// ## Argumentos
+/// - `widths`: Ancho de cada columna.
+/// - `values`: Valores de la fila.
+fn txt_row(widths: &[usize], values: &[String]) -> String {
+    let cells: Vec<String> = widths.iter().enumerate()
+        .map(|(i, w)| format!(" {:<w$} ", values.get(i).map_or("", |v| v.as_str())))
+        .collect();
+    format!("|{}|", cells.join("|"))
+}
+
+/// Genera y escribe una tabla de texto formateada.
 ///
 /// Convierte los encabezados y registros proporcionados en un formato de tabla
-/// que es legible en la consola.
+/// que es legible en la consola. El ancho de cada columna se calcula a partir de la
+/// definición del campo (`FieldDefinition.len`) y de su título, de modo que las filas
+/// se escriben a medida que se procesan. En los formatos multi-registro se imprime
+/// una tabla por cada tipo de registro, precedida por su nombre.
 ///
 /// # Argumentos
-/// * `headers`: Un vector de Strings para los encabezados de las columnas.
-/// * `records`: Un vector de vectores de Strings, donde cada vector interno es una fila de datos.
+/// * `records`: Flujo de registros procesados.
+/// * `output`: Destino de la salida.
 ///
 /// # Retorno
 /// `Result<(), Box<dyn Error>>`: Retorna Ok(()) en caso de éxito o un Error.
 pub fn write_txt_output(
-    headers: Vec<String>,
-    records: Vec<Vec<String>>,
+    mut records: RecordStream,
+    output: &mut OutputSink,
 ) -> Result<(), Box<dyn Error>> {
     
-    let mut table = Table::new();
-    
-    table.set_format(*format::consts::FORMAT_DEFAULT);
-    let header_cells: Vec<Cell> = headers.into_iter()
-        .map(|h| Cell::new(&h).style_spec("b")) // 'b' para negrilla (bold)
-        .collect();
-        
-    table.add_row(Row::new(header_cells));
+    let mut output = BufWriter::new(output);
+    let record_types = std::mem::take(&mut records.record_types);
+    let options = records.options;
 
-    for record in records {
-        let data_cells: Vec<Cell> = record.into_iter()
-            .map(|v| Cell::new(&v))
-            .collect();
-            
-        table.add_row(Row::new(data_cells));
-    }
-    table.printstd();
-    
+    write_sections(&mut output, record_types.len(), records,
+        |out, type_index, _| {
+            let record_type = &record_types[type_index];
+            if !record_type.name.is_empty() {
+                writeln!(out, "\n{}:", record_type.name)?;
+            }
+            writeln!(out, "{}", txt_separator(&record_type.widths, '-'))?;
+            writeln!(out, "{}", txt_row(&record_type.widths, &record_type.headers))?;
+            writeln!(out, "{}", txt_separator(&record_type.widths, '='))
+        },
+        |out, type_index, values| {
+            let record_type = &record_types[type_index];
+            let widths = &record_type.widths;
+            writeln!(out, "{}", txt_row(widths, &record_type.render(values, options)))?;
+            writeln!(out, "{}", txt_separator(widths, '-'))
+        },
+        |_, _, _| Ok(()),
+    )?;
+
+    output.flush()?;
     Ok(())
 }
 
-/// Genera y escribe en stdout un documento HTML con una tabla de resultados.
+/// Genera y escribe un documento HTML con una tabla de resultados.
 ///
 /// Convierte los encabezados y registros proporcionados en la estructura
-/// <table>, <tr>, <th>, y <td> de HTML.
+/// `<table>`, `<tr>`, `<th>` y `<td>` de HTML. En los formatos multi-registro se genera
+/// una tabla por cada tipo de registro, con su nombre como subtítulo (`<h2>`).
 ///
 /// # Argumentos
-/// * `headers`: Un vector de Strings para los encabezados de las columnas.
-/// * `records`: Un vector de vectores de Strings, donde cada vector interno es una fila de datos.
+/// * `records`: Flujo de registros procesados.
+/// * `output`: Destino de la salida.
 ///
 /// # Retorno
 /// `Result<(), Box<dyn Error>>`: Retorna Ok(()) en caso de éxito o un Error.
 pub fn write_html_output(
-    headers: Vec<String>,
-    records: Vec<Vec<String>>,
+    mut records: RecordStream,
+    output: &mut OutputSink,
 ) -> Result<(), Box<dyn Error>> {
     
-    let mut output = io::stdout().lock();
+    let mut output = BufWriter::new(output);
+    let record_types = std::mem::take(&mut records.record_types);
+    let options = records.options;
 
     // 1. Escribir el encabezado del documento HTML
     writeln!(output, "<!DOCTYPE html>")?;
@@ -326,11 +1951,6 @@ pub fn write_html_output(
     // ⭐ Modificación del Estilo ⭐
     writeln!(output, "  <style>")?;
     // Base de la tabla
-
-    let css = "
-
-    ";
-
     writeln!(output, "    table {{ border-collapse: break-word; margin: 20px; table-layout: auto; width: auto; }}")?; // Ajusta el ancho de la tabla y celdas
     writeln!(output, "    th, td {{ border: 1px solid #ddd; padding: 8px; text-align: left; }}")?;
     
@@ -349,32 +1969,370 @@ pub fn write_html_output(
     writeln!(output, "</head>")?;
     writeln!(output, "<body>")?;
     writeln!(output, "  <h1>Resultados Procesados</h1>")?;
-    writeln!(output, "  <table>")?;
 
-    // 2. Generar los encabezados (<thead> / <tr> / <th>)
-    writeln!(output, "    <thead>")?;
-    write!(output, "      <tr>")?;
-    for header in headers {
-        write!(output, "<th>{}</th>", header)?;
-    }
-    writeln!(output, "</tr>")?;
-    writeln!(output, "    </thead>")?;
+    write_sections(&mut output, record_types.len(), records,
+        |out, type_index, _| {
+            let record_type = &record_types[type_index];
+            if !record_type.name.is_empty() {
+                writeln!(out, "  <h2>{}</h2>", record_type.name)?;
+            }
+            writeln!(out, "  <table>")?;
 
-    // 3. Generar el cuerpo de la tabla (<tbody> / <tr> / <td>)
-    writeln!(output, "    <tbody>")?;
-    for record in records {
-        write!(output, "      <tr>")?;
-        for value in record {
-            write!(output, "<td>{}</td>", value)?;
-        }
-        writeln!(output, "</tr>")?;
-    }
-    writeln!(output, "    </tbody>")?;
+            // 2. Generar los encabezados (<thead> / <tr> / <th>)
+            writeln!(out, "    <thead>")?;
+            write!(out, "      <tr>")?;
+            for header in record_type.headers.iter() {
+                write!(out, "<th>{}</th>", header)?;
+            }
+            writeln!(out, "</tr>")?;
+            writeln!(out, "    </thead>")?;
+            writeln!(out, "    <tbody>")
+        },
+        // 3. Generar el cuerpo de la tabla (<tbody> / <tr> / <td>)
+        |out, type_index, values| {
+            write!(out, "      <tr>")?;
+            for value in record_types[type_index].render(values, options) {
+                write!(out, "<td>{}</td>", value)?;
+            }
+            writeln!(out, "</tr>")
+        },
+        |out, _, _| {
+            writeln!(out, "    </tbody>")?;
+            writeln!(out, "  </table>")
+        },
+    )?;
 
     // 4. Cerrar las etiquetas
-    writeln!(output, "  </table>")?;
     writeln!(output, "</body>")?;
     writeln!(output, "</html>")?;
 
+    output.flush()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Archivo de datos de prueba en memoria.
+    fn input_from(content: impl AsRef<[u8]>) -> DataInput {
+        DataInput::from_reader("prueba.txt", Box::new(std::io::Cursor::new(content.as_ref().to_vec()))).unwrap()
+    }
+
+    /// Esquema de prueba con un formato de retenciones y su tabla de lookup.
+    fn retenciones_schema() -> ConfigSchema {
+        let mut schema: ConfigSchema = toon_format::decode_default(r#"formats:
+  retenciones:
+    category: Pruebas
+    delimiter: ""
+    fields[4]{nombre,len,tipo,param1,param2}:
+      "Tipo",1,table,tipo_operacion,""
+      "Nombre",10,string,"",""
+      "Fecha",8,date,"%Y%m%d",""
+      "Importe",10,zamount,"2",""
+tables:
+  tipo_operacion:
+    "1": Retención
+    "2": Percepción
+"#).unwrap();
+        schema.resolve().unwrap();
+        schema
+    }
+
+    /// Registros del formato de retenciones a partir de un archivo en memoria.
+    fn retenciones<'a>(schema: &'a ConfigSchema, content: &str) -> RecordStream<'a> {
+        crate::parse::parse_to_records(input_from(content), &schema.formats["retenciones"], schema, false, false, false)
+    }
+
+    /// Escribe una salida en un archivo temporal y devuelve su contenido.
+    fn written(write: impl FnOnce(&mut OutputSink) -> Result<(), Box<dyn Error>>) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("salida");
+        let mut output = OutputSink::create(Some(&path)).unwrap();
+        write(&mut output).unwrap();
+        output.commit().unwrap();
+        std::fs::read(&path).unwrap()
+    }
+
+    const RETENCIONES: &str = "1Juan      202310290000012345\n2Perez     000000000000000100\n";
+
+    #[test]
+    fn output_file_name_keeps_dotted_stems() {
+        let dir = Path::new("salidas");
+        assert_eq!(output_file_name("datos/ventas.txt", "csv", dir).unwrap(), dir.join("ventas.csv"));
+        assert_eq!(output_file_name("datos/ventas.2023-01.txt", "csv", dir).unwrap(), dir.join("ventas.2023-01.csv"));
+        assert_eq!(output_file_name("datos/ventas.2023-02.txt", "csv", dir).unwrap(), dir.join("ventas.2023-02.csv"));
+    }
+
+    #[test]
+    fn output_file_name_strips_compression_extension() {
+        let dir = Path::new("salidas");
+        assert_eq!(output_file_name("padron.txt.gz", "json", dir).unwrap(), dir.join("padron.json"));
+        assert_eq!(output_file_name("padron.2024.txt.zip", "sqlite", dir).unwrap(), dir.join("padron.2024.db"));
+        assert_eq!(output_file_name("-", "csv", dir).unwrap(), dir.join("stdin.csv"));
+    }
+
+    #[test]
+    fn output_sink_only_creates_the_file_on_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ventas.csv");
+
+        let mut output = OutputSink::create(Some(&path)).unwrap();
+        output.write_all(b"a medias").unwrap();
+        drop(output);
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let mut output = OutputSink::create(Some(&path)).unwrap();
+        output.write_all(b"completa").unwrap();
+        output.commit().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"completa");
+    }
+
+    #[test]
+    fn output_file_name_rejects_outputs_without_files() {
+        assert!(output_file_name("ventas.txt", "term", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn line_length_stats_ignores_blank_lines_and_trailing_spaces() {
+        let stats = line_length_stats(input_from("12345\r\n123  \n\n12345\n1234567\n"), Encoding::default()).unwrap();
+        assert_eq!((stats.lines, stats.min, stats.max, stats.mode), (4, 3, 7, 5));
+    }
+
+    #[test]
+    fn line_length_stats_splits_ebcdic_lines_on_nl() {
+        let content = [0xC8, 0x96, 0x93, 0x81, 0x40, 0x40, 0x15, 0xF1, 0xF2, 0xF3, 0x15];
+        let stats = line_length_stats(input_from(content), Encoding::Cp037).unwrap();
+        assert_eq!((stats.lines, stats.min, stats.max, stats.mode), (2, 3, 4, 3));
+    }
+
+    #[test]
+    fn read_input_rows_decodes_csv_with_the_given_encoding() {
+        let csv = b"Nombre;C\xf3digo\r\n\"Pe\xf1a; Juan\";01\r\n";
+        let rows: Vec<InputRow> = read_input_rows(input_from(csv), ";", Encoding::Windows1252).unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["Nombre"], "Peña; Juan");
+        assert_eq!(rows[0]["Código"], "01");
+    }
+
+    #[test]
+    fn read_input_rows_reports_invalid_bytes_with_their_line() {
+        let mut rows = read_input_rows(input_from(b"Nombre\nJuan\nPe\xf1a\n"), ",", Encoding::Utf8).unwrap();
+
+        assert_eq!(rows.next().unwrap().unwrap()["Nombre"], "Juan");
+        let error = rows.next().unwrap().unwrap_err().to_string();
+        assert!(error.starts_with("Línea 3:"), "{}", error);
+    }
+
+    #[test]
+    fn read_input_rows_rejects_delimiters_outside_the_encoding() {
+        assert!(read_input_rows(input_from("a€b\n"), "€", Encoding::Iso8859_1).is_err());
+    }
+
+    #[test]
+    fn read_input_rows_reads_json_lines() {
+        let json = "{\"Nombre\": \"Peña\", \"Importe\": 12.5, \"Nota\": null}\n{\"Nombre\": \"Juan\"}\n";
+        let input = DataInput::from_reader("filas.jsonl", Box::new(std::io::Cursor::new(json.as_bytes().to_vec()))).unwrap();
+        let rows: Vec<InputRow> = read_input_rows(input, ",", Encoding::Windows1252).unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+
+        assert_eq!(rows[0]["Nombre"], "Peña");
+        assert_eq!(rows[0]["Importe"], "12.5");
+        assert_eq!(rows[0]["Nota"], "");
+        assert_eq!(rows[1]["Nombre"], "Juan");
+    }
+
+    #[test]
+    fn write_sqlite_output_loads_typed_rows_and_lookup_tables() {
+        let schema = retenciones_schema();
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("retenciones.db");
+        let mut sql_options = SqlOptions {
+            dialect: SqlDialect::Sqlite,
+            table_name: "retenciones".to_string(),
+            batch_size: 500,
+            copy: false,
+            drop_table: true,
+            database: Some(database.to_string_lossy().into_owned()),
+            append: false,
+        };
+
+        write_sqlite_output(retenciones(&schema, RETENCIONES), &sql_options, &schema).unwrap();
+        sql_options.append = true;
+        write_sqlite_output(retenciones(&schema, RETENCIONES), &sql_options, &schema).unwrap();
+
+        let connection = Connection::open(&database).unwrap();
+        let (count, total): (i64, f64) = connection
+            .query_row("SELECT COUNT(*), ROUND(SUM(importe), 2) FROM retenciones", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((count, total), (4, 248.9));
+
+        let (tipo, fecha): (String, Option<String>) = connection
+            .query_row("SELECT tipo, fecha FROM retenciones WHERE nombre = 'Perez' LIMIT 1", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((tipo.as_str(), fecha), ("2", None));
+
+        let description: String = connection
+            .query_row("SELECT descripcion FROM ref_tipo_operacion WHERE codigo = '1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(description, "Retención");
+    }
+
+    #[test]
+    fn write_sqlite_output_requires_a_database() {
+        let schema = retenciones_schema();
+        let sql_options = SqlOptions {
+            dialect: SqlDialect::Sqlite,
+            table_name: "retenciones".to_string(),
+            batch_size: 500,
+            copy: false,
+            drop_table: true,
+            database: None,
+            append: false,
+        };
+        assert!(write_sqlite_output(retenciones(&schema, RETENCIONES), &sql_options, &schema).is_err());
+    }
+
+    #[test]
+    fn write_json_output_writes_typed_values() {
+        let schema = retenciones_schema();
+        let ndjson = written(|output| write_json_output(retenciones(&schema, RETENCIONES), false, output));
+        let lines: Vec<serde_json::Value> = ndjson.lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(lines[0], serde_json::json!({
+            "Tipo": {"code": "1", "description": "Retención"},
+            "Nombre": "Juan",
+            "Fecha": "2023-10-29",
+            "Importe": 123.45,
+        }));
+        assert_eq!(lines[1]["Fecha"], serde_json::Value::Null);
+
+        let array = written(|output| write_json_output(retenciones(&schema, RETENCIONES), true, output));
+        let array: Vec<serde_json::Value> = serde_json::from_slice(&array).unwrap();
+        assert_eq!(array, lines);
+    }
+
+    #[test]
+    fn json_keys_disambiguate_repeated_names() {
+        let field = |nombre: &str| FieldDefinition {
+            nombre: nombre.to_string(),
+            len: 1,
+            tipo: "string".to_string(),
+            param1: String::new(),
+            param2: String::new(),
+        };
+        let fields = [field("Monto"), field("Monto"), field("record_type")];
+
+        assert_eq!(json_keys(&fields, false), ["\"Monto\"", "\"Monto_2\"", "\"record_type\""]);
+        assert_eq!(json_keys(&fields, true), ["\"Monto\"", "\"Monto_2\"", "\"record_type_2\""]);
+    }
+
+    #[test]
+    fn write_xlsx_output_writes_one_typed_sheet_per_format() {
+        let schema = retenciones_schema();
+        let xlsx = written(|output| write_xlsx_output(retenciones(&schema, RETENCIONES), "retenciones", output));
+
+        let mut archive = ZipArchive::new(std::io::Cursor::new(xlsx)).unwrap();
+        let mut workbook = String::new();
+        archive.by_name("xl/workbook.xml").unwrap().read_to_string(&mut workbook).unwrap();
+        assert!(workbook.contains("name=\"retenciones\""));
+
+        let mut sheet = String::new();
+        archive.by_name("xl/worksheets/sheet1.xml").unwrap().read_to_string(&mut sheet).unwrap();
+        assert!(sheet.contains("<v>123.45</v>"), "{}", sheet);
+        assert!(sheet.contains("Juan"));
+        assert!(sheet.contains("<autoFilter ref=\"A1:D3\"/>"));
+    }
+
+    #[test]
+    fn write_arrow_and_parquet_outputs_read_back_with_the_format_schema() {
+        let schema = retenciones_schema();
+
+        let arrow = written(|output| write_arrow_output(retenciones(&schema, RETENCIONES), output));
+        let reader = arrow::ipc::reader::StreamReader::try_new(std::io::Cursor::new(arrow), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+        assert_eq!(batches[0].schema().field(3).data_type(), &arrow::datatypes::DataType::Decimal128(10, 2));
+
+        let mut parquet = tempfile::tempfile().unwrap();
+        parquet.write_all(&written(|output| write_parquet_output(retenciones(&schema, RETENCIONES), output))).unwrap();
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(parquet)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+        assert_eq!(batches[0].schema().field(2).data_type(), &arrow::datatypes::DataType::Date32);
+    }
+
+    /// Lee el archivo de datos completo, tal como lo entrega `DataInput::into_reader`.
+    fn read_all(input: DataInput) -> Vec<u8> {
+        let mut content = Vec::new();
+        input.into_reader().read_to_end(&mut content).unwrap();
+        content
+    }
+
+    const PADRON: &[u8] = b"20231029Primera   \r\n20231030Segunda   \r\n";
+
+    #[test]
+    fn data_input_decompresses_gzip_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("padron.txt.gz");
+        let mut encoder = flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::default());
+        encoder.write_all(PADRON).unwrap();
+        encoder.finish().unwrap();
+
+        let input = DataInput::open(&path.to_string_lossy()).unwrap();
+        assert_eq!(input.sample_lines(Encoding::Windows1252), [b"20231029Primera   ".to_vec(), b"20231030Segunda   ".to_vec()]);
+        assert_eq!(read_all(input), PADRON);
+    }
+
+    #[test]
+    fn data_input_extracts_zip_files_with_a_single_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_zip = |name: &str, entries: &[&str]| {
+            let path = dir.path().join(name);
+            let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+            for entry in entries {
+                zip.start_file(*entry, zip::write::SimpleFileOptions::default()).unwrap();
+                zip.write_all(PADRON).unwrap();
+            }
+            zip.finish().unwrap();
+            path.to_string_lossy().into_owned()
+        };
+
+        let single = write_zip("padron.zip", &["padron.txt"]);
+        assert_eq!(read_all(DataInput::open(&single).unwrap()), PADRON);
+
+        let several = write_zip("varios.zip", &["enero.txt", "febrero.txt"]);
+        let error = DataInput::open(&several).err().unwrap().to_string();
+        assert!(error.contains("contiene 2 archivos (enero.txt, febrero.txt)"), "{}", error);
+    }
+
+    #[test]
+    fn data_input_drops_the_bom_and_keeps_the_sample_for_the_reader() {
+        let mut content = UTF8_BOM.to_vec();
+        content.extend_from_slice(PADRON);
+        let input = input_from(&content);
+
+        assert_eq!(input.sample_lines(Encoding::Utf8).len(), 2);
+        assert_eq!(read_all(input), PADRON);
+    }
+
+    #[test]
+    fn sample_lines_skip_blank_lines_and_stop_at_the_sample_size() {
+        let lines: Vec<String> = (1..=DETECTION_SAMPLE_LINES + 5).map(|n| format!("linea {}\n\n", n)).collect();
+        let input = input_from(lines.concat());
+        let sample = input.sample_lines(Encoding::Windows1252);
+
+        assert_eq!(sample.len(), DETECTION_SAMPLE_LINES);
+        assert_eq!(sample[1], b"linea 2");
+    }
+}
@@ -0,0 +1,54 @@
+//! parseit: Biblioteca para interpretar archivos de longitud fija del ARCA
+//! (entre otros formatos).
+//!
+//! Permite cargar el esquema de formatos (`parseit.toon`), deducir el formato de un
+//! archivo de datos y recorrer sus registros con valores tipados (`Value`). La
+//! herramienta de línea de comandos `parseit` está construida sobre esta API.
+//!
+//! ## Ejemplo
+//! ```no_run
+//! use parseit::{Value, deduce_format, load_config_from_paths, read_records, record_layouts};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let schema = load_config_from_paths()?;
+//! let format_name = deduce_format("data.dat", &schema)?;
+//! let format_def = &schema.formats[&format_name];
+//!
+//! for record in read_records("data.dat", format_def, &schema)? {
+//!     let record = record?;
+//!     let (_, fields) = record_layouts(format_def)[record.record_type];
+//!     for (field, value) in fields.iter().zip(record.values.iter()) {
+//!         match value {
+//!             Value::Decimal(amount) => println!("{}: {}", field.nombre, amount),
+//!             Value::Coded { code, description } => println!("{}: {} {:?}", field.nombre, code, description),
+//!             other => println!("{}: {}", field.nombre, other.render(field, false, false)),
+//!         }
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+pub mod config;
+pub mod parse;
+pub mod io;
+pub mod encode;
+pub mod sql;
+pub mod columnar;
+pub mod encoding;
+
+pub use chrono::NaiveDate;
+pub use rust_decimal::Decimal;
+
+pub use config::{
+    CONFIG_FILE, ConfigIssue, ConfigSchema, FieldDefinition, FormatDefinition, RecordDefinition,
+    check_config, format_versions, load_config, load_config_from_paths, record_layouts,
+    split_format_name,
+};
+pub use parse::{
+    Record, Records, ValidationError, Value, deduce_format, deduce_format_from, read_records,
+    read_records_from, select_format_version, validate_file, validate_file_from,
+};
+pub use io::DataInput;
+pub use encoding::Encoding;
+pub use encode::encode_record;
@@ -4,23 +4,33 @@
 //! deducir formatos automáticamente, parsear archivos de datos y generar salidas en varios formatos
 //! (CSV, terminal interactivo).
 //!  
-mod config;
-mod parse;
-mod io;
-
 use clap::Parser;
+use std::collections::HashMap;
 use std::error::Error;
+use std::path::{Path, PathBuf};
 use prettytable::{Table, format, row};
-use crate::parse::{deduce_format, parse_to_records};
-use crate::io::{write_output};
-use crate::config::{CONFIG_FILE, ConfigSchema, FormatDefinition, calculate_format_length};
+use parseit::parse::{best_candidates, concat_record_streams, deduce_format_from, detection_encoding, parse_to_records, score_formats, select_format_version};
+use parseit::io::{DataInput, line_length_stats, output_file_name, write_encoded_output, write_output, write_validation_report};
+use parseit::sql::{SqlDialect, SqlOptions};
+use parseit::encoding::Encoding;
+use parseit::config::{CONFIG_FILE, ConfigIssue, ConfigSchema, Severity, VERSION_SEPARATOR, calculate_format_length, check_config, format_versions, load_layered_config, record_layouts};
 
 // Estructura de ayuda para almacenar y ordenar los datos
 struct FormatData<'a> {
     category: String,
     name: &'a String,
     count: usize,
-    total_len: usize,
+    total_len: String,
+    validity: String,
+    source: String,
+}
+
+// Resultado del procesamiento de cada archivo, para el resumen de los lotes
+struct FileResult {
+    data_file: String,
+    format_name: Option<String>,
+    // Salida generada o motivo del error
+    outcome: Result<String, String>,
 }
 
 const PROGRAM_AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
@@ -50,13 +60,35 @@ salida variados."#,
 {all-args}{after-help}" 
 )]
 struct Args {
-    /// Ruta al archivo de datos de longitud fija a procesar.
-    #[arg(name = "data_file")]
-    data_file: String,
+    /// Rutas a los archivos de datos de longitud fija a procesar ("-" para la entrada
+    /// estándar). Admite patrones (ej: "ventas/*.txt") y archivos comprimidos con gzip o
+    /// zip. Con más de un archivo cada uno genera su propia salida (ver --output-dir),
+    /// salvo con --concat.
+    #[arg(name = "data_file", required_unless_present_any = ["show_formats", "check_config"])]
+    data_files: Vec<String>,
 
-    /// Nombre del formato a usar de 'parseit.toon' (ej: "sample").
+    /// Concatena los registros de todos los archivos de datos en una única salida, con las
+    /// columnas source_file (archivo de origen) y line (número de línea). Todos los archivos
+    /// deben ser del mismo formato; los demás se informan en el resumen y se omiten.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["output_dir", "validate", "encode"])]
+    concat: bool,
+
+    /// Nombre del formato a usar de 'parseit.toon' (ej: "sample"). En los formatos con
+    /// versiones se puede fijar una (ej: "compras-comprobantes@rg3685-v2") o indicar sólo
+    /// el nombre para elegir la vigente en la fecha del archivo.
     #[arg(short, long)]
     format_name: Option<String>,
+
+    /// Archivo de configuración adicional. Se carga después de los 'parseit.toon' del
+    /// directorio del ejecutable, del usuario y del proyecto, y sus formatos y tablas
+    /// reemplazan a los del mismo nombre.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Codificación de los archivos de datos: windows-1252, iso-8859-1, utf-8, cp037 o
+    /// cp284 (EBCDIC). Reemplaza a la declarada en cada formato de 'parseit.toon'.
+    #[arg(long)]
+    encoding: Option<Encoding>,
     
     /// Delimitador para la salida CSV (por defecto es ',').
     #[arg(long, short='c', default_value = ",")]
@@ -68,18 +100,73 @@ struct Args {
     ///    csv -> Valores separados por coma
     ///    term -> Visualización interactiva por medio de cvlens
     ///     sql -> Script de creación e inserción de filas en una tabla 
+    ///    sqlite -> Carga directa en una base SQLite
+    ///    xlsx -> Libro de Excel con celdas tipadas
+    ///    parquet -> Archivo Parquet con columnas tipadas
+    ///    arrow -> Flujo Arrow IPC con columnas tipadas
+    ///    json -> Arreglo JSON con un objeto por registro
+    ///    ndjson -> Un objeto JSON por línea
     #[arg(long, short='o', default_value = "csv",
         // 1. **help:** La descripción corta que aparecerá en la columna.
-        help = "Tipo de salida (csv, term, sql)", 
+        help = "Tipo de salida (csv, term, sql, sqlite, xlsx, parquet, arrow, json, ndjson, txt, html)", 
         
         // 2. **long_help:** La descripción detallada con la lista de formatos.
         long_help = "Tipo de salida.\n\n\
                      Formatos soportados:\n\
                      - csv: Valores separados por coma.\n\
                      - term: Visualización interactiva con cvlens.\n\
-                     - sql: Script de creación e inserción de filas.")]
+                     - sql: Script de creación e inserción de filas.\n\
+                     - sqlite: Carga directa en la base indicada con --database.\n\
+                     - xlsx: Libro de Excel con celdas tipadas (redirigir a un archivo).\n\
+                     - parquet: Archivo Parquet con columnas tipadas (redirigir a un archivo).\n\
+                     - arrow: Flujo Arrow IPC con columnas tipadas (redirigir a un archivo).\n\
+                     - json: Arreglo JSON con un objeto por registro.\n\
+                     - ndjson: Un objeto JSON por línea (JSON Lines).\n\
+                     - txt: Tabla de texto.\n\
+                     - html: Tabla HTML.")]
     output_type: String,
 
+    /// Archivo de salida (por defecto, la salida estándar). Se escribe en un temporal que
+    /// recién toma el nombre final al terminar, así un error no deja una salida a medias.
+    #[arg(long, conflicts_with = "output_dir")]
+    output: Option<PathBuf>,
+
+    /// Directorio de las salidas del procesamiento por lotes: cada archivo de datos genera
+    /// una salida con su nombre y la extensión del tipo de salida (ej: ventas.txt → ventas.csv).
+    /// Con varios archivos de datos y sin --output-dir se usa el directorio actual.
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Dialecto de la salida SQL: postgres, sqlite, sqlserver o mysql.
+    #[arg(long, default_value = "postgres")]
+    sql_dialect: String,
+
+    /// Nombre de la tabla de la salida SQL (por defecto, el nombre del formato).
+    /// Puede incluir el esquema (ej: "staging.retenciones").
+    #[arg(long)]
+    table_name: Option<String>,
+
+    /// Cantidad de filas por sentencia INSERT de la salida SQL.
+    #[arg(long, default_value_t = 1)]
+    sql_batch_size: usize,
+
+    /// Carga los registros con bloques COPY en lugar de INSERT (sólo postgres).
+    #[arg(long, default_value_t = false)]
+    sql_copy: bool,
+
+    /// Omite el DROP TABLE IF EXISTS previo a la creación de la tabla.
+    #[arg(long, default_value_t = false)]
+    sql_no_drop: bool,
+
+    /// Archivo de la base SQLite en la que se cargan los registros con `-o sqlite`.
+    #[arg(long)]
+    database: Option<String>,
+
+    /// Agrega los registros a la tabla existente de la base SQLite (si sus columnas
+    /// coinciden con las del formato) en lugar de reemplazarla.
+    #[arg(long, default_value_t = false)]
+    append: bool,
+
     /// Genera la salida en formato largo (transpuesto): NumeroFila, NombreColumna, Valor
     #[arg(long, short='l', default_value_t = false)]
     long_format: bool, 
@@ -92,15 +179,40 @@ struct Args {
     #[arg(long, short='t', default_value_t = false)]
     dont_use_tables: bool,
 
-    /// Mostrar los formatos soportados
+    /// Mostrar los formatos soportados. Con -f muestra el diseño completo del formato
+    /// (con la herencia y los grupos de campos ya resueltos).
     #[arg(short = 's', long, default_value_t = false)] 
     show_formats: bool,
+
+    /// Revisa los formatos de 'parseit.toon' (tipos de campo, tablas, decimales, nombres
+    /// repetidos, longitudes y patrones de fecha) e informa cada problema con su ubicación.
+    /// Termina con error si encuentra alguno que impida procesar un formato.
+    #[arg(long, default_value_t = false)]
+    check_config: bool,
+
+    /// Explica la deducción del formato: longitudes esperadas y observadas, líneas
+    /// válidas de la muestra y motivo de descarte de cada formato.
+    #[arg(long, default_value_t = false)]
+    explain_detection: bool,
+
+    /// Valida cada línea contra el formato (longitud, montos, fechas y códigos de tablas)
+    /// y emite un reporte CSV de errores. Termina con error si encuentra alguno.
+    #[arg(long, default_value_t = false)]
+    validate: bool,
+
+    /// Genera un archivo del formato indicado con -f a partir de un CSV o JSON cuyas
+    /// columnas coinciden con los nombres de los campos, y lo escribe en la salida estándar
+    /// o en el archivo de --output. El CSV se lee en la codificación del formato (o la de --encoding).
+    #[arg(long, short = 'e', default_value_t = false)]
+    encode: bool,
 }
 
-/// Función auxiliar para mostrar los formatos usando prettytable y ordenando por categoría/nombre
+/// Función auxiliar para mostrar los formatos usando prettytable y ordenando por categoría/nombre.
+/// Antes de la tabla se listan los archivos de configuración cargados y, para cada formato,
+/// se indica el archivo del que proviene.
 /// 
 /// ## Argumentos
-/// - `formats`: Mapa de nombres de formatos a sus definiciones.
+/// - `schema`: Esquema de configuración cargado.
 /// 
 /// ## Retorno
 /// Nada. Imprime la tabla directamente en la salida estándar.
@@ -110,24 +222,31 @@ struct Args {
 ///
 /// ## Ejemplo
 /// ```
-/// display_available_formats(&schema.formats);
+/// display_available_formats(&schema);
 /// ```
-fn display_available_formats(formats: &std::collections::HashMap<String, FormatDefinition>) {
+fn display_available_formats(schema: &ConfigSchema) {
     let mut table = Table::new();
     
     // 1. Definir los encabezados
-    table.add_row(row![bFg->"CATEGORÍA", bFg->"NOMBRE DEL FORMATO", bFg->"Nº DE CAMPOS", bFg->"LONGITUD TOTAL"]); 
+    table.add_row(row![bFg->"CATEGORÍA", bFg->"NOMBRE DEL FORMATO", bFg->"Nº DE CAMPOS", bFg->"LONGITUD TOTAL", bFg->"VIGENCIA", bFg->"ARCHIVO"]); 
     table.set_format(*format::consts::FORMAT_BOX_CHARS);
 
     // 2. Pre-procesar los datos y construir el vector de FormatData
-    let mut processed_data: Vec<FormatData> = formats.iter()
+    let mut processed_data: Vec<FormatData> = schema.formats.iter()
         .map(|(name, definition)| {
             let category = definition.category.clone();
+            // En los formatos multi-registro se listan las longitudes de cada tipo de registro
+            let layouts = record_layouts(definition);
             FormatData {
                 category,
                 name,
-                count: definition.fields.len(),
-                total_len: calculate_format_length(&definition.fields), 
+                count: layouts.iter().map(|(_, fields)| fields.len()).sum(),
+                total_len: layouts.iter()
+                    .map(|(_, fields)| calculate_format_length(fields).to_string())
+                    .collect::<Vec<_>>()
+                    .join("/"), 
+                validity: definition.validity(),
+                source: schema.format_sources.get(name).map(|p| p.display().to_string()).unwrap_or_default(),
             }
         })
         .collect();
@@ -137,7 +256,7 @@ fn display_available_formats(formats: &std::collections::HashMap<String, FormatD
         // Ordenar por categoría (String)
         a.category.cmp(&b.category)
             // Si las categorías son iguales, ordenar por nombre de formato (String)
-            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.name.cmp(b.name))
     });
 
     // 4. Llenar la tabla
@@ -146,14 +265,196 @@ fn display_available_formats(formats: &std::collections::HashMap<String, FormatD
             data.category, 
             data.name, 
             data.count, 
-            data.total_len
+            data.total_len,
+            data.validity,
+            data.source
         ]);
     }
 
-    println!("\n▶️ Formatos disponibles en 'parseit.toon':\n");
+    println!("\n▶️ Archivos de configuración (de menor a mayor prioridad):\n");
+    for layer in schema.layers.iter() {
+        println!("   {:<9} {}", layer.scope, layer.path.display());
+    }
+
+    println!("\n▶️ Formatos disponibles en '{}':\n", CONFIG_FILE);
+    table.printstd();
+}
+
+/// Función auxiliar para mostrar el diseño resuelto de un formato: cada campo con su
+/// posición, longitud, tipo y parámetros, después de aplicar la herencia (`extends`) y
+/// expandir los grupos de campos. En los formatos multi-registro se muestra una tabla por
+/// tipo de registro.
+/// 
+/// ## Argumentos
+/// - `schema`: Esquema de configuración cargado.
+/// - `format_name`: Nombre del formato a mostrar.
+/// 
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Imprime las tablas directamente en la salida estándar.
+/// 
+/// ## Errores
+/// Retorna un error si el formato no existe (o si tiene versiones y no se indicó una).
+///
+/// ## Ejemplo
+/// ```
+/// display_format_layout(&schema, "ventas-comprobantes")?;
+/// ```
+fn display_format_layout(schema: &ConfigSchema, format_name: &str) -> Result<(), Box<dyn Error>> {
+    let versions = format_versions(schema, format_name);
+    let definition = schema.formats.get(format_name).ok_or_else(|| if versions.is_empty() {
+        format!("El formato '{}' no se encontró en {}", format_name, CONFIG_FILE)
+    } else {
+        format!(
+            "El formato '{}' tiene versiones ({}). Indique una con -f {}{}versión.",
+            format_name, versions.join(", "), format_name, VERSION_SEPARATOR
+        )
+    })?;
+
+    println!("\n▶️ Formato '{}'\n", format_name);
+    println!("   Categoría:     {}", definition.category);
+    println!("   Delimitador:   {}", if definition.delimiter.is_empty() { "(longitud fija)" } else { &definition.delimiter });
+    println!("   Codificación:  {}", definition.encoding);
+    if !definition.extends.is_empty() {
+        println!("   Extiende a:    {}", definition.extends);
+    }
+    if !definition.validity().is_empty() {
+        println!("   Vigencia:      {}", definition.validity());
+    }
+    if !definition.date_field.is_empty() {
+        println!("   Campo fecha:   {}", definition.date_field);
+    }
+    if let Some(source) = schema.format_sources.get(format_name) {
+        println!("   Archivo:       {}", source.display());
+    }
+
+    for (record_name, fields) in record_layouts(definition) {
+        let mut table = Table::new();
+        table.add_row(row![bFg->"#", bFg->"CAMPO", bFg->"DESDE", bFg->"HASTA", bFg->"LONGITUD", bFg->"TIPO", bFg->"PARAM1", bFg->"PARAM2"]); 
+        table.set_format(*format::consts::FORMAT_BOX_CHARS);
+
+        // Las posiciones sólo tienen sentido en los formatos de longitud fija
+        let mut start = 1;
+        for (index, field) in fields.iter().enumerate() {
+            let (from, to) = if definition.delimiter.is_empty() {
+                (start.to_string(), (start + field.len - 1).to_string())
+            } else {
+                ("-".to_string(), "-".to_string())
+            };
+            start += field.len;
+            table.add_row(row![index + 1, field.nombre, r->from, r->to, r->field.len, field.tipo, field.param1, field.param2]);
+        }
+
+        if record_name.is_empty() {
+            println!("\n▶️ Diseño ({} bytes):\n", calculate_format_length(fields));
+        } else {
+            println!("\n▶️ Registro '{}' ({} bytes):\n", record_name, calculate_format_length(fields));
+        }
+        table.printstd();
+    }
+
+    Ok(())
+}
+
+/// Función auxiliar para mostrar los problemas encontrados al revisar la configuración,
+/// uno por fila, con su gravedad, formato, ubicación, regla y detalle.
+/// 
+/// ## Argumentos
+/// - `issues`: Problemas encontrados por `check_config`.
+/// 
+/// ## Retorno
+/// Nada. Imprime la tabla directamente en la salida estándar.
+///
+/// ## Ejemplo
+/// ```
+/// display_config_issues(&check_config(&schema));
+/// ```
+fn display_config_issues(issues: &[ConfigIssue]) {
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    println!("\n▶️ Revisión de '{}': {} errores, {} advertencias\n", CONFIG_FILE, errors, issues.len() - errors);
+
+    if issues.is_empty() {
+        return;
+    }
+
+    let mut table = Table::new();
+    table.add_row(row![bFg->"GRAVEDAD", bFg->"FORMATO", bFg->"UBICACIÓN", bFg->"REGLA", bFg->"DETALLE"]); 
+    table.set_format(*format::consts::FORMAT_BOX_CHARS);
+
+    for issue in issues.iter() {
+        table.add_row(row![issue.severity, issue.format, issue.location, issue.rule, issue.detail]);
+    }
+
     table.printstd();
 }
 
+/// Función auxiliar para explicar la deducción del formato de un archivo.
+/// Muestra las longitudes de línea observadas en el archivo completo y, para cada
+/// formato, su longitud esperada, cuántas líneas de la muestra validan campo por
+/// campo, su puntaje y el motivo por el que fue descartado (si corresponde).
+/// 
+/// ## Argumentos
+/// - `data_file`: Ruta al archivo de datos.
+/// - `schema`: Esquema de configuración cargado.
+/// 
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Imprime el reporte en la salida estándar.
+/// 
+/// ## Errores
+/// Retorna un error si no se puede abrir o leer el archivo.
+///
+/// ## Ejemplo
+/// ```
+/// display_detection_report("data.dat", &schema)?;
+/// ```
+fn display_detection_report(data_file: &str, schema: &ConfigSchema) -> Result<(), Box<dyn Error>> {
+    let input = DataInput::open(data_file)?;
+    let scores = score_formats(&input, schema);
+    let stats = line_length_stats(input, detection_encoding(schema))?;
+    let best = best_candidates(&scores);
+    let sample_lines = scores.iter().map(|s| s.sample_lines).max().unwrap_or(0);
+
+    println!("\n▶️ Deducción del formato de '{}':\n", data_file);
+    println!("   Líneas: {}  Longitud mínima: {}  máxima: {}  más frecuente: {}", 
+        stats.lines, stats.min, stats.max, stats.mode);
+    println!("   Muestra: {} líneas\n", sample_lines);
+
+    let mut table = Table::new();
+    table.add_row(row![bFg->"FORMATO", bFg->"LONGITUD ESPERADA", bFg->"LÍNEAS VÁLIDAS", bFg->"PUNTAJE", bFg->"RESULTADO"]); 
+    table.set_format(*format::consts::FORMAT_BOX_CHARS);
+
+    for score in scores.iter() {
+        let definition = &schema.formats[&score.name];
+        let expected = if definition.delimiter.is_empty() {
+            record_layouts(definition).iter()
+                .map(|(_, fields)| calculate_format_length(fields).to_string())
+                .collect::<Vec<_>>()
+                .join("/")
+        } else {
+            record_layouts(definition).iter()
+                .map(|(_, fields)| format!("{} campos", fields.len()))
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+
+        let result = match &score.rejection {
+            Some(reason) => format!("Descartado: {}", reason),
+            None if best.len() == 1 && best[0].name == score.name => "Elegido".to_string(),
+            None if best.iter().any(|b| b.name == score.name) => "Empate".to_string(),
+            None => "Puntaje menor".to_string(),
+        };
+
+        table.add_row(row![
+            score.name,
+            expected,
+            format!("{}/{}", score.valid_lines, score.sample_lines),
+            score.score(),
+            result
+        ]);
+    }
+
+    table.printstd();
+    Ok(())
+}
 
 // --------------------------------------------------------------------------------------------------------
 // --- Función Principal ---
@@ -163,50 +464,506 @@ fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
     // Cargar la configuración
-    let schema: ConfigSchema = match config::load_config_from_paths() {
+    let mut schema: ConfigSchema = match load_layered_config(args.config.as_deref()) {
         Ok(s) => s,
         Err(e) => {
             return Err(e);
         }
     };
 
+    // La codificación de la línea de comandos reemplaza a la de cada formato
+    if let Some(encoding) = args.encoding {
+        for definition in schema.formats.values_mut() {
+            definition.encoding = encoding;
+        }
+    }
+
     // --- LÓGICA DE MOSTRAR FORMATOS Y SALIR ---
     if args.show_formats {
-        display_available_formats(&schema.formats);
+        match &args.format_name {
+            Some(format_name) => display_format_layout(&schema, format_name)?,
+            None => display_available_formats(&schema),
+        }
         return Ok(()); // Salir del programa inmediatamente
     }
     // ----------------------------------------
 
-    if args.data_file.is_empty() {
+    if args.check_config {
+        let issues = check_config(&schema);
+        display_config_issues(&issues);
+
+        let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+        if errors > 0 {
+            return Err(format!("Se encontraron {} errores en '{}'.", errors, CONFIG_FILE).into());
+        }
+        return Ok(());
+    }
+
+    if args.data_files.iter().any(|data_file| data_file.is_empty()) {
         return Err("Error: Debe proporcionar la ruta al archivo de datos que se quiere procesar.".into());
     }    
 
-    let actual_format_name = if let Some(name) = args.format_name {
-        name
+    // Se expanden los patrones; los que no coinciden con ningún archivo se informan en el resumen
+    let (data_files, mut results) = expand_data_files(&args.data_files);
+
+    if args.explain_detection {
+        for data_file in data_files.iter() {
+            display_detection_report(data_file, &schema)?;
+        }
+        return Ok(());
+    }
+
+    if args.encode && args.format_name.is_none() {
+        return Err("El modo --encode requiere indicar el formato a generar con --format-name.".into());
+    }
+
+    // Un único archivo se procesa directamente, sin resumen
+    let single_file = !args.concat && args.output_dir.is_none() && results.is_empty() && data_files.len() == 1;
+    if single_file {
+        let input = DataInput::open(&data_files[0])?;
+        let format_name = resolve_format(&args, &schema, &input)?;
+        return process_file(&args, &schema, input, &format_name, args.output.as_deref());
+    }
+
+    let output_result = if args.concat {
+        process_concatenated(&args, &schema, &data_files, &mut results)
+    } else {
+        process_split(&args, &schema, &data_files, &mut results)
+    };
+
+    display_batch_summary(&results);
+    output_result?;
+
+    let failures = results.iter().filter(|r| r.outcome.is_err()).count();
+    if failures > 0 {
+        return Err(format!("No se pudieron procesar {} de {} archivos.", failures, results.len()).into());
+    }
+    Ok(())
+}
+
+/// Expande los patrones de archivos de datos (ej: `ventas/*.txt`) en las rutas que
+/// coinciden, ordenadas. Las rutas sin comodines, o que existen tal cual, se usan
+/// sin expandir.
+/// 
+/// ## Argumentos
+/// - `patterns`: Rutas o patrones indicados en la línea de comandos.
+/// 
+/// ## Retorno
+/// `(Vec<String>, Vec<FileResult>)` - Rutas a procesar (sin repetir) y los patrones
+/// inválidos o sin coincidencias, como resultados con error.
+///
+/// ## Ejemplo
+/// ```
+/// let (data_files, results) = expand_data_files(&["ventas/*.txt".to_string()]);
+/// ```
+fn expand_data_files(patterns: &[String]) -> (Vec<String>, Vec<FileResult>) {
+    let mut data_files: Vec<String> = Vec::new();
+    let mut failures = Vec::new();
+
+    for pattern in patterns {
+        let is_pattern = pattern.contains(['*', '?', '[']) && !Path::new(pattern).exists();
+        if !is_pattern {
+            if !data_files.contains(pattern) {
+                data_files.push(pattern.clone());
+            }
+            continue;
+        }
+
+        let matches: Result<Vec<PathBuf>, String> = glob::glob(pattern)
+            .map_err(|e| format!("Patrón inválido: {}", e))
+            .and_then(|paths| paths.collect::<Result<_, _>>().map_err(|e| e.to_string()));
+
+        let outcome = match matches {
+            Ok(paths) if paths.is_empty() => Err("Ningún archivo coincide con el patrón.".to_string()),
+            Ok(paths) => {
+                for path in paths.into_iter().filter(|p| p.is_file()) {
+                    let path = path.to_string_lossy().to_string();
+                    if !data_files.contains(&path) {
+                        data_files.push(path);
+                    }
+                }
+                continue;
+            }
+            Err(e) => Err(e),
+        };
+        failures.push(FileResult { data_file: pattern.clone(), format_name: None, outcome });
+    }
+
+    (data_files, failures)
+}
+
+/// Procesa cada archivo de datos por separado: cada uno genera su propia salida, con
+/// el nombre del archivo y la extensión del tipo de salida, en el directorio de
+/// `--output-dir` (o el directorio actual). Un archivo cuya salida ya generó otro del
+/// lote (ej: `a/ventas.txt` y `b/ventas.txt`) se registra como error y no se procesa.
+/// 
+/// ## Argumentos
+/// - `args`: Argumentos de la línea de comandos.
+/// - `schema`: Esquema de configuración cargado.
+/// - `data_files`: Rutas a los archivos de datos.
+/// - `results`: Resultados del lote, donde se agrega el de cada archivo.
+/// 
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Ok si se pudo recorrer el lote. Los errores de cada
+/// archivo se registran en `results`.
+/// 
+/// ## Errores
+/// Retorna un error si se indicó --output, que admite un único archivo de datos, o si
+/// no se puede crear el directorio de salida.
+fn process_split(
+    args: &Args,
+    schema: &ConfigSchema,
+    data_files: &[String],
+    results: &mut Vec<FileResult>,
+    ) -> Result<(), Box<dyn Error>> {
+
+    if args.output.is_some() {
+        return Err("--output admite un único archivo de datos; use --output-dir o --concat para procesar varios.".into());
+    }
+
+    // La extensión de cada salida depende del modo: reporte CSV, archivo generado o tipo de salida
+    let output_type = if args.validate {
+        "csv"
+    } else if args.encode {
+        "txt"
     } else {
-        deduce_format(&args.data_file, &schema.formats)?
+        args.output_type.as_str()
     };
+    let output_dir = args.output_dir.clone().unwrap_or_default();
+    if !output_dir.as_os_str().is_empty() {
+        std::fs::create_dir_all(&output_dir)
+            .map_err(|e| format!("No se puede crear el directorio de salida '{}': {}", output_dir.display(), e))?;
+    }
+
+    // Salidas ya generadas en el lote y el archivo de datos de cada una: dos archivos con
+    // el mismo nombre en distintos directorios no deben reemplazarse uno al otro
+    let mut written: HashMap<PathBuf, &String> = HashMap::new();
+
+    for data_file in data_files {
+        let mut format_name = None;
+        let outcome = DataInput::open(data_file)
+            .and_then(|input| {
+                let name = format_name.insert(resolve_format(args, schema, &input)?);
+                let output_path = output_file_name(data_file, output_type, &output_dir)?;
+                if let Some(previous) = written.get(&output_path) {
+                    return Err(format!(
+                        "La salida '{}' ya se generó para '{}'; renombre uno de los archivos o procéselos por separado.",
+                        output_path.display(), previous
+                    ).into());
+                }
+                process_file(args, schema, input, name, Some(&output_path))?;
+                written.insert(output_path.clone(), data_file);
+                Ok(output_path.display().to_string())
+            })
+            .map_err(|e| e.to_string());
+
+        results.push(FileResult { data_file: data_file.clone(), format_name, outcome });
+    }
+
+    Ok(())
+}
+
+/// Concatena los registros de todos los archivos de datos en una única salida (la
+/// salida estándar o el archivo de --output), con las columnas `source_file` y `line`
+/// (ver `concat_record_streams`). El formato de la salida es el indicado con
+/// --format-name o el del primer archivo cuyo formato se pudo deducir; los archivos de
+/// otro formato se registran como errores y se omiten.
+/// 
+/// ## Argumentos
+/// - `args`: Argumentos de la línea de comandos.
+/// - `schema`: Esquema de configuración cargado.
+/// - `data_files`: Rutas a los archivos de datos.
+/// - `results`: Resultados del lote, donde se agrega el de cada archivo.
+/// 
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Ok si la salida se generó.
+/// 
+/// ## Errores
+/// Retorna un error si ningún archivo pudo incluirse o si falla la escritura de la
+/// salida (en ese caso no se genera el archivo de --output).
+fn process_concatenated(
+    args: &Args,
+    schema: &ConfigSchema,
+    data_files: &[String],
+    results: &mut Vec<FileResult>,
+    ) -> Result<(), Box<dyn Error>> {
+
+    let mut output_format: Option<String> = None;
+    let mut streams = Vec::new();
+    let mut included = Vec::new();
+
+    for data_file in data_files {
+        let opened = DataInput::open(data_file)
+            .and_then(|input| Ok((resolve_format(args, schema, &input)?, input)));
+        let (format_name, input) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                results.push(FileResult { data_file: data_file.clone(), format_name: None, outcome: Err(e.to_string()) });
+                continue;
+            }
+        };
+
+        let output_name = output_format.get_or_insert_with(|| format_name.clone());
+        let stream = if *output_name != format_name {
+            Err(format!("Su formato es distinto del de la salida concatenada ('{}').", output_name))
+        } else {
+            schema.formats.get(&format_name)
+                .ok_or_else(|| format!("El formato '{}' no se encontró en {}", format_name, CONFIG_FILE))
+                .map(|format_def| parse_to_records(
+                    input,
+                    format_def,
+                    schema,
+                    args.format_numeric,
+                    args.dont_use_tables,
+                    args.long_format,
+                ))
+        };
+
+        // El resultado de los archivos incluidos se completa al escribir la salida
+        let outcome = match stream {
+            Ok(stream) => {
+                streams.push((data_file.clone(), stream));
+                included.push(results.len());
+                Ok(String::new())
+            }
+            Err(e) => Err(e),
+        };
+        results.push(FileResult { data_file: data_file.clone(), format_name: Some(format_name), outcome });
+    }
+
+    let Some(format_name) = output_format.filter(|_| !streams.is_empty()) else {
+        return Err("Ningún archivo de datos pudo incluirse en la salida concatenada.".into());
+    };
+
+    let output = args.output.as_ref().map_or("salida estándar".to_string(), |path| path.display().to_string());
+    let written = write_output(
+        &args.output_type,
+        concat_record_streams(streams),
+        &args.delim_character,
+        &sql_options(args, &format_name)?,
+        schema,
+        &format_name,
+        args.output.as_deref(),
+    );
+
+    let outcome = match &written {
+        Ok(()) => Ok(output),
+        Err(e) => Err(e.to_string()),
+    };
+    for index in included {
+        results[index].outcome = outcome.clone();
+    }
+
+    written
+}
+
+/// Muestra en la salida de errores el resumen de un lote: el formato y la salida de cada
+/// archivo, o el motivo por el que falló (deducción del formato, validación, escritura).
+/// 
+/// ## Argumentos
+/// - `results`: Resultado de cada archivo del lote.
+fn display_batch_summary(results: &[FileResult]) {
+    let mut table = Table::new();
+    table.add_row(row![bFg->"ARCHIVO", bFg->"FORMATO", bFg->"RESULTADO"]);
+    table.set_format(*format::consts::FORMAT_BOX_CHARS);
+
+    for result in results {
+        let outcome = match &result.outcome {
+            Ok(output) => format!("OK → {}", output),
+            Err(e) => format!("Error: {}", e),
+        };
+        table.add_row(row![result.data_file, result.format_name.as_deref().unwrap_or("-"), outcome]);
+    }
+
+    let failures = results.iter().filter(|r| r.outcome.is_err()).count();
+    eprintln!("\n▶️ Resumen: {} archivos, {} con errores:\n", results.len(), failures);
+    // El resumen va a la salida de errores para no mezclarse con la salida de los registros
+    let _ = table.print(&mut std::io::stderr());
+}
+
+/// Determina el formato de un archivo de datos: el indicado con --format-name o el
+/// deducido a partir de sus primeras líneas. Si se indica un formato con versiones sin
+/// la versión (ej: "compras-comprobantes"), se elige la vigente en la fecha del archivo.
+/// 
+/// ## Argumentos
+/// - `args`: Argumentos de la línea de comandos.
+/// - `schema`: Esquema de configuración cargado.
+/// - `input`: Archivo de datos abierto.
+/// 
+/// ## Retorno
+/// `Result<String, Box<dyn Error>>` - Nombre del formato.
+/// 
+/// ## Errores
+/// Retorna un error si no se puede deducir el formato o elegir su versión.
+fn resolve_format(args: &Args, schema: &ConfigSchema, input: &DataInput) -> Result<String, Box<dyn Error>> {
+    match &args.format_name {
+        Some(name) if schema.formats.contains_key(name) => Ok(name.clone()),
+        // Un formato con versiones, indicado sin versión, se elige por la fecha del archivo
+        Some(name) if !format_versions(schema, name).is_empty() => {
+            if args.encode {
+                return Err(format!(
+                    "El formato '{}' tiene versiones ({}). Indique una con -f {}{}versión.",
+                    name, format_versions(schema, name).join(", "), name, VERSION_SEPARATOR
+                ).into());
+            }
+            select_format_version(input, schema, name)
+        }
+        Some(name) => Ok(name.clone()),
+        None => deduce_format_from(input, schema),
+    }
+}
+
+/// Arma las opciones de la salida SQL (y de la carga en SQLite) a partir de los argumentos.
+/// 
+/// ## Argumentos
+/// - `args`: Argumentos de la línea de comandos.
+/// - `format_name`: Nombre del formato, tabla por defecto.
+/// 
+/// ## Errores
+/// Retorna un error si el dialecto no es reconocido.
+fn sql_options(args: &Args, format_name: &str) -> Result<SqlOptions, Box<dyn Error>> {
+    Ok(SqlOptions {
+        dialect: args.sql_dialect.parse::<SqlDialect>()?,
+        table_name: args.table_name.clone().unwrap_or_else(|| format_name.to_string()),
+        batch_size: args.sql_batch_size,
+        copy: args.sql_copy,
+        drop_table: !args.sql_no_drop,
+        database: args.database.clone(),
+        append: args.append,
+    })
+}
+
+/// Procesa un archivo de datos con su formato y, según el modo, lo valida, lo genera a
+/// partir de un CSV o JSON o escribe sus registros en el tipo de salida pedido.
+/// 
+/// ## Argumentos
+/// - `args`: Argumentos de la línea de comandos.
+/// - `schema`: Esquema de configuración cargado.
+/// - `input`: Archivo de datos abierto.
+/// - `actual_format_name`: Nombre del formato del archivo (ver `resolve_format`).
+/// - `output_path`: Archivo de salida (`None` para la salida estándar).
+/// 
+/// ## Retorno
+/// `Result<(), Box<dyn Error>>` - Ok si la operación es exitosa, o un error en caso contrario.
+/// 
+/// ## Errores
+/// Retorna un error si el formato no existe, si la validación o la codificación
+/// encuentran errores o si falla la escritura de la salida.
+///
+/// ## Ejemplo
+/// ```
+/// process_file(&args, &schema, DataInput::open("ventas.txt")?, "ventas-comprobantes", Some(Path::new("ventas.csv")))?;
+/// ```
+fn process_file(
+    args: &Args,
+    schema: &ConfigSchema,
+    input: DataInput,
+    actual_format_name: &str,
+    output_path: Option<&Path>,
+    ) -> Result<(), Box<dyn Error>> {
+
+    let data_file = input.path().to_string();
 
     // Obtener el formato específico
-    let format_def = schema.formats.get(&actual_format_name)
+    let format_def = schema.formats.get(actual_format_name)
         .ok_or_else(|| format!("El formato '{}' no se encontró en {}", actual_format_name, CONFIG_FILE))?;
 
 
-    let (headers, records) = parse_to_records(
-        &args.data_file,
-        &format_def.fields, // campos del formato
-        &schema,            // tablas de lookup
+    if args.validate {
+        let error_count = write_validation_report(input, format_def, schema, &args.delim_character, output_path)?;
+        if error_count > 0 {
+            return Err(format!(
+                "Se encontraron {} errores de validación en '{}' (formato '{}').",
+                error_count, data_file, actual_format_name
+            ).into());
+        }
+        eprintln!("Validación correcta: '{}' respeta el formato '{}'.", data_file, actual_format_name);
+        return Ok(());
+    }
+
+    if args.encode {
+        let error_count = write_encoded_output(input, format_def, schema, &args.delim_character, output_path)?;
+        if error_count > 0 {
+            return Err(format!(
+                "Se encontraron {} errores al codificar '{}' con el formato '{}'; no se generó la salida.",
+                error_count, data_file, actual_format_name
+            ).into());
+        }
+        return Ok(());
+    }
+
+    let records = parse_to_records(
+        input,
+        format_def,         // formato seleccionado
+        schema,             // tablas de lookup
         args.format_numeric,
         args.dont_use_tables,
         args.long_format,
-    )?;    
+    );    
 
     write_output(
         &args.output_type,
-        headers,
         records,
-        &args.delim_character
-    )?;    
-    
-    Ok(())
+        &args.delim_character,
+        &sql_options(args, actual_format_name)?,
+        schema,
+        actual_format_name,
+        output_path,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMPORTES: &str = r#"formats:
+  importes:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Fecha",8,date,"%Y%m%d",""
+      "Detalle",10,string,"",""
+"#;
+
+    /// Crea un archivo de datos de prueba (y sus directorios) dentro de `dir`.
+    fn data_file(dir: &Path, name: &str) -> String {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "20231029Primera   \n").unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn expand_data_files_expands_patterns_without_repeating_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let enero = data_file(dir.path(), "ventas-01.txt");
+        let febrero = data_file(dir.path(), "ventas-02.txt");
+        let pattern = |p: &str| dir.path().join(p).to_string_lossy().into_owned();
+
+        let (data_files, failures) = expand_data_files(&[pattern("ventas-*.txt"), enero.clone(), pattern("compras-*.txt")]);
+
+        assert_eq!(data_files, [enero, febrero]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].data_file, pattern("compras-*.txt"));
+        assert!(failures[0].outcome.is_err());
+    }
+
+    #[test]
+    fn process_split_reports_outputs_already_written_in_the_batch() {
+        let mut schema: ConfigSchema = toon_format::decode_default(IMPORTES).unwrap();
+        schema.resolve().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let data_files = [data_file(dir.path(), "a/ventas.txt"), data_file(dir.path(), "b/ventas.txt")];
+        let output_dir = dir.path().join("salidas");
+        let args = Args::parse_from([
+            "parseit", "-f", "importes", "--output-dir", &output_dir.to_string_lossy(), &data_files[0], &data_files[1],
+        ]);
+
+        let mut results = Vec::new();
+        process_split(&args, &schema, &data_files, &mut results).unwrap();
+
+        assert_eq!(results[0].outcome, Ok(output_dir.join("ventas.csv").display().to_string()));
+        assert!(results[1].outcome.as_ref().unwrap_err().contains("ya se generó"));
+        assert!(std::fs::read_to_string(output_dir.join("ventas.csv")).unwrap().contains("Primera"));
+    }
 }
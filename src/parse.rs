@@ -2,44 +2,171 @@
 //! Proporciona funciones para deducir el formato de un archivo de longitud fija
 //! y para parsear los datos aplicando lookups y formateo numérico.
 //! 
-use std::collections::HashMap;
-use std::fs::File;
 use std::error::Error;
 use rust_decimal::Decimal;
 use std::str::FromStr;
-use std::io::{BufReader, BufRead};
-use encoding_rs::WINDOWS_1252; // O usa ISO_8859_1
-use crate::config::{ConfigSchema, FieldDefinition, FormatDefinition, calculate_format_length};
-use crate::io::get_first_line_length;
+use std::fmt::Write as _;
+use std::borrow::Cow;
+use std::io::BufRead;
+use crate::config::{CONFIG_FILE, ConfigSchema, FieldDefinition, FormatDefinition, VERSION_SEPARATOR, calculate_format_length, format_versions, record_layouts, split_format_name};
+use crate::encoding::Encoding;
+use crate::io::DataInput;
+use chrono::NaiveDate;
 
-/// Formatea una cadena numérica de entrada basada en el tipo de campo y las opciones de salida.
+/// Patrón de salida usado para los campos `date` que no declaran `param2`.
+pub const DEFAULT_DATE_OUTPUT: &str = "%d-%m-%Y";
+
+/// Valor tipado de un campo, según el `tipo` de su definición.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Texto: campos `string` y valores que no respetan el tipo de su campo.
+    Text(String),
+    /// Monto (`zamount`, `amount`), con la escala de los decimales del campo.
+    Decimal(Decimal),
+    /// Fecha (`date`). `None` si no se informó (vacía o compuesta sólo por ceros).
+    Date(Option<NaiveDate>),
+    /// Código de una tabla de lookup (`table`) y su descripción, si figura en la tabla.
+    Coded { code: String, description: Option<String> },
+}
+
+impl Value {
+    /// Convierte el valor en el texto que muestran las salidas de parseit.
+    ///
+    /// ## Argumentos
+    /// - `field`: Definición del campo (para el patrón de salida de las fechas, `param2`).
+    /// - `format_numeric`: Indica si se debe aplicar formateo numérico con separadores.
+    /// - `dont_use_tables`: Indica si se debe mostrar sólo el código de las tablas de lookup.
+    ///
+    /// ## Retorno
+    /// String - Montos con coma decimal ("1234,56" o "1.234,56"), fechas con el patrón
+    /// de salida y códigos como "código - descripción".
+    ///
+    /// ## Ejemplo
+    /// ```
+    /// # use parseit::Value;
+    /// # use parseit::config::FieldDefinition;
+    /// let field = FieldDefinition {
+    ///     nombre: "Tipo".into(), len: 1, tipo: "table".into(), param1: "tipo_operacion".into(), param2: String::new(),
+    /// };
+    /// let value = Value::Coded { code: "1".into(), description: Some("Retención".into()) };
+    /// assert_eq!(value.render(&field, false, false), "1 - Retención");
+    /// assert_eq!(value.render(&field, false, true), "1");
+    /// ```
+    pub fn render(&self, field: &FieldDefinition, format_numeric: bool, dont_use_tables: bool) -> String {
+        match self {
+            Value::Text(text) => text.clone(),
+            Value::Decimal(number) => format_decimal_value(number, format_numeric),
+            Value::Date(Some(date)) => format_date_value(date, &field.param2),
+            Value::Date(None) => String::new(),
+            Value::Coded { code, description: Some(description) } if !dont_use_tables => {
+                format!("{code} - {description}")
+            }
+            Value::Coded { code, .. } => code.clone(),
+        }
+    }
+}
+
+/// Registro leído del archivo de datos, con sus valores tipados.
+/// - record_type: Índice del tipo de registro en `Records::layouts`.
+/// - line_number: Número de línea del archivo de datos.
+/// - values: Valores del registro, en el orden de los campos de su tipo.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub record_type: usize,
+    pub line_number: usize,
+    pub values: Vec<Value>,
+}
+
+/// Flujo de registros tipados. Las líneas se leen y procesan a medida que se
+/// consume el iterador.
+/// - layouts: Nombre y campos de cada tipo de registro del formato (ver `record_layouts`).
+pub struct Records<'a> {
+    pub layouts: Vec<(&'a str, &'a [FieldDefinition])>,
+    records: Box<dyn Iterator<Item = Result<Record, Box<dyn Error>>> + 'a>,
+}
+
+impl Iterator for Records<'_> {
+    type Item = Result<Record, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next()
+    }
+}
+
+/// Opciones con las que los valores tipados se convierten en texto en las salidas.
+/// - format_numeric: Aplicar separadores de miles a los montos.
+/// - dont_use_tables: Mostrar sólo el código de las tablas de lookup.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    pub format_numeric: bool,
+    pub dont_use_tables: bool,
+}
+
+/// Tipo de registro de la salida, con los encabezados de sus columnas.
+/// - name: Nombre del tipo de registro (vacío en los formatos de un solo tipo).
+/// - headers: Encabezados de las columnas.
+/// - widths: Ancho estimado de cada columna ya formateada (para salidas de ancho fijo).
+/// - fields: Definición del campo de cada columna (tipo, longitud y parámetros).
+pub struct RecordType {
+    pub name: String,
+    pub headers: Vec<String>,
+    pub widths: Vec<usize>,
+    pub fields: Vec<FieldDefinition>,
+}
+
+impl RecordType {
+    /// Convierte los valores de un registro de este tipo en texto, con `Value::render`.
+    ///
+    /// ## Argumentos
+    /// - `values`: Valores del registro.
+    /// - `options`: Opciones de formateo de la salida.
+    ///
+    /// ## Retorno
+    /// `Vec<String>` - Valores listos para escribir.
+    pub fn render(&self, values: &[Value], options: RenderOptions) -> Vec<String> {
+        values.iter()
+            .zip(self.fields.iter())
+            .map(|(value, field)| value.render(field, options.format_numeric, options.dont_use_tables))
+            .collect()
+    }
+}
+
+/// Flujo de registros procesados. Las líneas se leen y procesan a medida que se
+/// consume el iterador, por lo que el uso de memoria no depende del tamaño del archivo.
+/// Los valores se mantienen tipados; cada salida los convierte en texto (con
+/// `RecordType::render` y `options`) o los escribe con su tipo.
+pub struct RecordStream<'a> {
+    pub record_types: Vec<RecordType>,
+    pub options: RenderOptions,
+    records: Box<dyn Iterator<Item = Result<Record, Box<dyn Error>>> + 'a>,
+}
+
+impl Iterator for RecordStream<'_> {
+    type Item = Result<Record, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next()
+    }
+}
+
+/// Interpreta el valor crudo de un monto según el tipo de campo.
 /// ## Argumentos
 /// - `raw_value`: Valor crudo extraído del archivo de datos.
 /// - `field_type`: Tipo de dato (ej: "zamount", "amount", "numeric").
-/// - `format_numeric`: Indica si se debe aplicar formateo numérico con separadores.
 /// - `decimal_places`: Cantidad de decimales implícitos/deseados.
 /// 
 /// ## Retorno
-/// String - Retorna la cadena formateada según las reglas especificadas.
-/// 
-/// ## Errores
-/// No retorna errores, pero si la conversión falla, devuelve el valor crudo.
-/// 
-/// ## Ejemplo
-/// ```
-/// let formatted = format_field_value("00012345", "zamount", true, 2);
-/// assert_eq!(formatted, "123,45");
-/// ```
-fn format_field_value(
+/// `Option<Decimal>` - El monto con la escala de sus decimales, o `None` si el valor
+/// no es numérico o el tipo no es un monto. Un valor vacío se interpreta como cero.
+fn parse_amount_value(
     raw_value: &str,
     field_type: &str, // Ej: "zamount", "amount", "numeric"
-    format_numeric: bool, // Reformatear con separadores S/N
     decimal_places: usize, // Cantidad de decimales implícitos/deseados
-    ) -> String {
+    ) -> Option<Decimal> {
     let raw_trimmed = raw_value.trim();
 
     if raw_trimmed.is_empty() {
-        return if decimal_places > 0 { "0,00".to_string() } else { "0".to_string() };
+        return Some(Decimal::new(0, decimal_places as u32));
     }
 
     let mut number_string_for_decimal: String;
@@ -67,7 +194,7 @@ fn format_field_value(
             
             // 2. Calcular el índice del punto decimal.
             // Aquí len siempre será >= final_decimal_places, por lo que checked_sub no será None.
-            let index_of_dot = len.checked_sub(final_decimal_places).unwrap_or(0);
+            let index_of_dot = len.saturating_sub(final_decimal_places);
 
             let int_part = &integer_part[0..index_of_dot];
             let dec_part = &integer_part[index_of_dot..];
@@ -103,18 +230,26 @@ fn format_field_value(
                  final_decimal_places = 2; // Estándar de 2 para montos si no se especificó
             }
         }
-        _ => return raw_trimmed.to_string(), // Si no es numérico, retornar el valor crudo
+        _ => return None, // Si no es numérico, no hay monto
     }
     
-    // --- FASE 2: CONVERSIÓN, ESCALA Y FORMATEO DE SALIDA ---
-    let mut number = match Decimal::from_str(&number_string_for_decimal) {
-        Ok(d) => d,
-        Err(_) => return raw_value.to_string(),
-    };
+    // --- FASE 2: CONVERSIÓN Y ESCALA ---
+    let mut number = Decimal::from_str(&number_string_for_decimal).ok()?;
 
     // Ajustar la escala
     number.set_scale(final_decimal_places as u32).expect("Fallo al configurar la escala.");
 
+    Some(number)
+}
+
+/// Formatea un monto con coma decimal y, opcionalmente, punto como separador de miles.
+/// ## Argumentos
+/// - `number`: Monto a formatear.
+/// - `format_numeric`: Indica si se debe aplicar formateo numérico con separadores.
+/// 
+/// ## Retorno
+/// String - Retorna el monto formateado (ej: "1234,56" o "1.234,56").
+fn format_decimal_value(number: &Decimal, format_numeric: bool) -> String {
     if !format_numeric {
         // Devolver formato estándar (punto decimal)
         return number.to_string().replace('.', ",");
@@ -157,168 +292,1828 @@ fn format_field_value(
 }
 
 
-/// Procesa el archivo de datos de longitud fija, aplica lookups y formateo,
-/// y devuelve un vector de registros listos para imprimir.
-/// 
+/// Interpreta una fecha según el patrón con el que viene en el archivo.
+/// Los patrones siguen la sintaxis de `strftime` (ej: "%Y%m%d", "%d/%m/%Y").
+///
 /// ## Argumentos
-/// - `file_path`: Ruta al archivo de datos.
-/// - `fields`: Definiciones de campos del formato seleccionado.
-/// - `schema`: Esquema de configuración cargado.
-/// - `format_numeric`: Indica si se debe aplicar formateo numérico con separadores.
-/// - `dont_use_tables`: Indica si se deben evitar las tablas de lookup.
-/// - `long_format`: Indica si se debe devolver la salida en formato largo.
-/// 
+/// - `raw_value`: Valor crudo extraído del archivo de datos.
+/// - `input_pattern`: Patrón con el que viene la fecha en el archivo (`param1`).
+///
 /// ## Retorno
-/// `Result<(Vec<String>, Vec<Vec<String>>), Box<dyn Error>>` -
-/// Tupla con encabezados y registros procesados, o un error.
-/// 
+/// `Result<Option<NaiveDate>, String>` - La fecha, o la descripción del error.
+/// Los valores vacíos o compuestos sólo por ceros se consideran fechas sin informar
+/// y devuelven `None`.
+///
 /// ## Errores
-/// Retorna un error si no se puede abrir o leer el archivo.
-/// 
-/// ## Ejemplo
-/// ```
-/// let (headers, records) = parse_to_records("data.dat", &fields, &schema, true, false, false)?;
-/// ``` 
-pub fn parse_to_records(file_path: &str, 
-                        fields: &[FieldDefinition],
-                        schema: &ConfigSchema,
-                        format_numeric: bool,
-                        dont_use_tables: bool,
-                        long_format: bool,
-                    ) -> Result<(Vec<String>, Vec<Vec<String>>), Box<dyn Error>> {
-    
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
+/// Retorna un error si el valor no respeta el patrón de entrada.
+fn parse_date_value(raw_value: &str, input_pattern: &str) -> Result<Option<NaiveDate>, String> {
+    let raw_trimmed = raw_value.trim();
+
+    if raw_trimmed.is_empty() || raw_trimmed.chars().all(|c| c == '0') {
+        return Ok(None);
+    }
 
-    // 1. Obtener encabezados
-    let headers: Vec<String> = fields.iter().map(|f| f.nombre.clone()).collect();
-    let mut records: Vec<Vec<String>> = Vec::new();
+    NaiveDate::parse_from_str(raw_trimmed, input_pattern)
+        .map(Some)
+        .map_err(|e| format!("se esperaba el patrón '{}': {}", input_pattern, e))
+}
 
-    // 2. Iterar por las lineas del archivo
-    for line_result in reader.split(b'\n') {
-        
-        let buffer = line_result?;
-        
-        let (cow, _, _) = WINDOWS_1252.decode(&buffer);
-        let line = cow.to_string(); // Convertir a String propia
+/// Escribe una fecha con el patrón de salida de un campo.
+///
+/// ## Argumentos
+/// - `date`: Fecha a escribir.
+/// - `output_pattern`: Patrón de salida (`param2`). Si está vacío se usa `DEFAULT_DATE_OUTPUT`.
+///
+/// ## Retorno
+/// String - La fecha formateada. Si el patrón es inválido se usa el formato ISO ("%Y-%m-%d").
+fn format_date_value(date: &NaiveDate, output_pattern: &str) -> String {
+    let output_pattern = if output_pattern.is_empty() { DEFAULT_DATE_OUTPUT } else { output_pattern };
+
+    // `format` entra en pánico al mostrarse con un patrón inválido, por eso se escribe con `write!`
+    let mut formatted = String::new();
+    if write!(formatted, "{}", date.format(output_pattern)).is_err() {
+        return date.to_string();
+    }
+
+    formatted
+}
+
+/// Divide una línea de un formato delimitado en sus valores crudos.
+/// Los valores pueden venir entre comillas dobles, en cuyo caso el delimitador
+/// queda protegido y las comillas internas se escapan duplicándolas (`""`).
+///
+/// ## Argumentos
+/// - `line`: Línea ya decodificada, sin el salto de línea final.
+/// - `delimiter`: Delimitador de campos declarado en el formato (ej: ";").
+///
+/// ## Retorno
+/// `Vec<String>` - Valores de la línea, sin comillas envolventes.
+fn split_delimited_line(line: &str, delimiter: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        if in_quotes {
+            if c == '"' {
+                if rest[1..].starts_with('"') {
+                    // Comilla escapada dentro de un valor entre comillas
+                    current.push('"');
+                    rest = &rest[2..];
+                    continue;
+                }
+                in_quotes = false;
+            } else {
+                current.push(c);
+            }
+        } else if c == '"' && current.trim().is_empty() {
+            current.clear();
+            in_quotes = true;
+        } else if rest.starts_with(delimiter) {
+            values.push(std::mem::take(&mut current));
+            rest = &rest[delimiter.len()..];
+            continue;
+        } else {
+            current.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+
+    values.push(current);
+    values
+}
+
+/// Interpreta el valor crudo de un campo según su tipo: código y descripción de
+/// las tablas de lookup, fechas y montos.
+///
+/// ## Argumentos
+/// - `field`: Definición del campo.
+/// - `raw_value`: Valor crudo, ya recortado.
+/// - `line_number`: Número de línea (para las advertencias).
+/// - `schema`: Esquema de configuración cargado (tablas de lookup).
+///
+/// ## Retorno
+/// `Value` - Valor tipado. Las fechas y montos que no respetan su tipo se informan
+/// y se conservan como `Value::Text` con el valor crudo.
+fn parse_field_value(
+    field: &FieldDefinition,
+    raw_value: &str,
+    line_number: usize,
+    schema: &ConfigSchema,
+    ) -> Value {
+
+    match field.tipo.as_str() {
+        // ******* Lógica de Lookup (Tablas) *******
+        "table" => Value::Coded {
+            code: raw_value.to_string(),
+            description: schema.tables.get(&field.param1).and_then(|t| t.get(raw_value)).cloned(),
+        },
+
+        // ******* Lógica de Fechas *******
+        "date" => match parse_date_value(raw_value, &field.param1) {
+            Ok(date) => Value::Date(date),
+            Err(e) => {
+                eprintln!(
+                    "Advertencia: Línea {}: Campo '{}' con fecha inválida '{}' ({}).",
+                    line_number, field.nombre, raw_value, e
+                );
+                Value::Text(raw_value.to_string())
+            }
+        },
+
+        // ******* Lógica de Montos *******
+        "zamount" | "amount" => {
+            let decimal_places = field.param1.parse::<usize>().unwrap_or(2); // Decimales
+            match parse_amount_value(raw_value, &field.tipo, decimal_places) {
+                Some(number) => Value::Decimal(number),
+                None => Value::Text(raw_value.to_string()),
+            }
+        }
+
+        _ => Value::Text(raw_value.to_string()),
+    }
+}
+
+/// Determina qué tipo de registro de un formato corresponde a una línea.
+/// En los formatos de un solo tipo de registro siempre devuelve `Some(0)`.
+///
+/// Un tipo de registro coincide si su `prefix` aparece en la posición `offset` de la
+/// línea y, si declara `length`, la línea (sin el `\r` final) tiene esa longitud.
+///
+/// ## Argumentos
+/// - `format_def`: Definición del formato.
+/// - `line`: Bytes crudos de la línea, sin el `\n` final.
+///
+/// ## Retorno
+/// `Option<usize>` - Índice del tipo de registro (en el orden de `record_layouts`),
+/// o `None` si ninguno coincide.
+fn select_record_layout(format_def: &FormatDefinition, line: &[u8]) -> Option<usize> {
+    if format_def.records.is_empty() {
+        return Some(0);
+    }
+
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+    format_def.records.iter().position(|record| {
+        if record.length.is_some_and(|length| length != line.len()) {
+            return false;
+        }
+
+        // Un prefijo no representable en la codificación del formato no coincide con ninguna línea
+        let Ok(prefix) = format_def.encoding.encode(&record.prefix) else {
+            return false;
+        };
+        line.get(record.offset..record.offset + prefix.len()) == Some(prefix.as_ref())
+    })
+}
+
+/// Decodifica los bytes de un campo (o de la línea completa) e informa, con su número
+/// de línea y posición, los bytes que no son válidos en la codificación del formato.
+/// Esos bytes se reemplazan por `\u{FFFD}`.
+///
+/// ## Argumentos
+/// - `encoding`: Codificación del formato.
+/// - `bytes`: Bytes crudos del campo o de la línea.
+/// - `line_number`: Número de línea (para las advertencias).
+/// - `start_pos`: Posición de `bytes` en la línea (desde 0).
+/// - `field_name`: Nombre del campo (`None` si `bytes` es la línea completa).
+///
+/// ## Retorno
+/// `Cow<str>` - El texto decodificado.
+fn decode_reporting<'b>(
+    encoding: Encoding,
+    bytes: &'b [u8],
+    line_number: usize,
+    start_pos: usize,
+    field_name: Option<&str>,
+    ) -> Cow<'b, str> {
+
+    match encoding.decode(bytes) {
+        Ok(text) => text,
+        Err(offset) => {
+            let subject = match field_name {
+                Some(name) => format!("Campo '{}' con bytes", name),
+                None => "Bytes".to_string(),
+            };
+            eprintln!(
+                "Advertencia: Línea {}: {} no válidos en {} (posición {}); se reemplazaron por '\u{FFFD}'.",
+                line_number, subject, encoding, start_pos + offset + 1
+            );
+            encoding.decode_lossy(bytes)
+        }
+    }
+}
 
+/// Describe la diferencia entre la cantidad de valores de una línea delimitada y la de
+/// campos del formato. Los campos que faltan quedan sin valor y los valores sobrantes se
+/// descartan.
+///
+/// ## Argumentos
+/// - `line_number`: Número de línea.
+/// - `expected`: Cantidad de campos del formato.
+/// - `found`: Cantidad de valores de la línea.
+///
+/// ## Retorno
+/// `Option<String>` - La advertencia, o `None` si las cantidades coinciden.
+fn field_count_warning(line_number: usize, expected: usize, found: usize) -> Option<String> {
+    match found.cmp(&expected) {
+        std::cmp::Ordering::Equal => None,
+        std::cmp::Ordering::Less => Some(format!(
+            "Línea {}: Se esperaban {} campos y se encontraron {}.", line_number, expected, found
+        )),
+        std::cmp::Ordering::Greater => Some(format!(
+            "Línea {}: Se esperaban {} campos y se encontraron {}; se descartan los sobrantes.",
+            line_number, expected, found
+        )),
+    }
+}
+
+/// Extrae y procesa los valores de una línea según las definiciones de campos.
+///
+/// ## Argumentos
+/// - `buffer`: Bytes crudos de la línea, sin el salto de línea final.
+/// - `fields`: Definiciones de campos del tipo de registro.
+/// - `delimiter`: Delimitador del formato (vacío para longitud fija).
+/// - `encoding`: Codificación del formato.
+/// - `line_number`: Número de línea (para las advertencias).
+/// - `schema`: Esquema de configuración cargado.
+///
+/// ## Retorno
+/// `Vec<Value>` - Valores tipados del registro.
+fn parse_line(
+    buffer: &[u8],
+    fields: &[FieldDefinition],
+    delimiter: &str,
+    encoding: Encoding,
+    line_number: usize,
+    schema: &ConfigSchema,
+    ) -> Vec<Value> {
+
+    let mut record_parts = Vec::new();
+
+    if delimiter.is_empty() {
+        // Las posiciones se calculan sobre los bytes del registro original, antes de
+        // decodificar, así los caracteres multibyte (UTF-8) no desplazan los campos siguientes.
         let mut start_pos = 0;
-        let mut record_parts = Vec::new();
 
-        // 3. Procesamos cada columna
         for field in fields.iter() {
             let end_pos = start_pos + field.len;
 
             // Asegurarse de no exceder la longitud de la línea
-            if end_pos > line.len() {
-                eprintln!("Advertencia: Línea demasiado corta. Campo '{}' incompleto.", field.nombre);
-                record_parts.push("".to_string());
+            if end_pos > buffer.len() {
+                eprintln!("Advertencia: Línea {}: Campo '{}' incompleto, la línea es demasiado corta.", line_number, field.nombre);
+                record_parts.push(Value::Text(String::new()));
                 break;
             }
 
-            let raw_value = line[start_pos..end_pos].trim().to_string();
-            let mut final_value = raw_value.clone();
-
-            // ******* Lógica de Lookup (Tablas) *******
-            let should_lookup = !dont_use_tables;
-            if field.tipo == "table" && should_lookup {
-                let table_name = &field.param1; 
-                if let Some(table) = schema.tables.get(table_name) {
-                    if let Some(lookup_value) = table.get(&raw_value) {
-                        // Concatenar valor crudo y descripción
-                        final_value = format!("{raw_value} - {lookup_value}");
-                    }
-                }
-            }
+            let cow = decode_reporting(encoding, &buffer[start_pos..end_pos], line_number, start_pos, Some(&field.nombre));
+            let raw_value = cow.trim();
 
-            // ***************************************** // Aplicar formateo numérico si es necesario
-            if field.tipo == "zamount" || field.tipo == "amount" {
-                final_value = format_field_value(&final_value, 
-                                                &field.tipo, 
-                                                format_numeric, 
-                                                field.param1.parse::<usize>().unwrap_or(2) // Decimales
-                ); 
-            }
-            
-            // 4. Almacenar el valor final
-            record_parts.push(final_value);
+            record_parts.push(parse_field_value(field, raw_value, line_number, schema));
             start_pos = end_pos;
         }
+    } else {
+        let line = decode_reporting(encoding, buffer, line_number, 0, None);
+        let values = split_delimited_line(line.trim_end_matches(['\r', '\n']), delimiter);
 
-        records.push(record_parts);
-    }
+        if let Some(warning) = field_count_warning(line_number, fields.len(), values.len()) {
+            eprintln!("Advertencia: {}", warning);
+        }
 
-    // Si se solicita formato largo, aplanamos los registros aquí y devolvemos
-    // encabezado y registros ya listos para escribir (cada fila tendrá
-    // tres columnas: número de fila, nombre de columna y valor).
-    if long_format {
-        let flat_headers = vec!["#".to_string(), "Columna".to_string(), "Valor".to_string()];
-        let mut flat_records: Vec<Vec<String>> = Vec::new();
-
-        for (row_index, record) in records.iter().enumerate() {
-            let row_num = (row_index + 1).to_string();
-            for (col_index, value) in record.iter().enumerate() {
-                let col_name = headers.get(col_index).cloned().unwrap_or_else(|| format!("col_{}", col_index + 1));
-                flat_records.push(vec![row_num.clone(), col_name, value.clone()]);
+        for (field, value) in fields.iter().zip(values.iter()) {
+            let raw_value = value.trim();
+
+            // En los formatos delimitados, `len` es el ancho máximo admitido
+            if raw_value.chars().count() > field.len {
+                eprintln!(
+                    "Advertencia: Línea {}: Campo '{}' excede la longitud máxima de {}.",
+                    line_number, field.nombre, field.len
+                );
             }
-        }
 
-        return Ok((flat_headers, flat_records));
+            record_parts.push(parse_field_value(field, raw_value, line_number, schema));
+        }
     }
 
-    Ok((headers, records))
+    record_parts
 }
 
+/// Estima el ancho de un campo una vez formateado, para las salidas de ancho fijo.
+/// Contempla la descripción de las tablas de lookup, los separadores de los montos
+/// y el patrón de salida de las fechas.
+///
+/// ## Argumentos
+/// - `field`: Definición del campo.
+/// - `schema`: Esquema de configuración cargado (tablas de lookup).
+/// - `dont_use_tables`: Indica si se deben evitar las tablas de lookup.
+///
+/// ## Retorno
+/// usize - Ancho estimado en caracteres (al menos el del nombre del campo).
+fn column_width(field: &FieldDefinition, schema: &ConfigSchema, dont_use_tables: bool) -> usize {
+    let value_width = match field.tipo.as_str() {
+        "table" if !dont_use_tables => {
+            let description_width = schema.tables.get(&field.param1)
+                .and_then(|t| t.values().map(|d| d.chars().count()).max())
+                .unwrap_or(0);
+            field.len + 3 + description_width
+        }
+        // Signo, separadores de miles y coma decimal
+        "zamount" | "amount" => field.len + field.len / 3 + 2,
+        "date" => {
+            let pattern = if field.param2.is_empty() { DEFAULT_DATE_OUTPUT } else { &field.param2 };
+            let mut sample = String::new();
+            match NaiveDate::from_ymd_opt(2000, 12, 31).map(|d| write!(sample, "{}", d.format(pattern))) {
+                Some(Ok(())) => sample.chars().count(),
+                _ => field.len,
+            }
+        }
+        _ => field.len,
+    };
+
+    value_width.max(field.nombre.chars().count())
+}
 
-/// Intenta identificar el formato de un archivo de datos comparando la longitud 
-/// de su primer registro con las longitudes predefinidas en el esquema de configuración.
+/// Lee el archivo de datos y devuelve un flujo de registros con sus valores tipados.
+/// Las líneas se leen a medida que se consume el flujo.
+///
+/// Si el formato declara un `delimiter`, cada línea se divide por ese delimitador
+/// (respetando valores entre comillas) y el `len` de cada campo pasa a ser su
+/// ancho máximo. En caso contrario, los campos se extraen por posición (longitud fija).
 ///
-/// Este proceso es crucial para determinar qué conjunto de reglas de análisis (schema)
-/// debe aplicarse al archivo de longitud fija.
+/// En los formatos multi-registro (`records`), cada línea se interpreta con el tipo
+/// de registro cuyo discriminador coincide. Las líneas que no coinciden con ningún
+/// tipo se informan y se descartan.
 ///
+/// Las líneas se separan y decodifican según el `encoding` del formato; los bytes que no
+/// son válidos en esa codificación se informan con su número de línea.
+/// 
+/// ## Argumentos
+/// - `file_path`: Ruta al archivo de datos.
+/// - `format_def`: Definición del formato seleccionado.
+/// - `schema`: Esquema de configuración cargado.
+/// 
+/// ## Retorno
+/// `Result<Records, Box<dyn Error>>` - Flujo de registros tipados, o un error.
+/// 
+/// ## Errores
+/// Retorna un error si no se puede abrir el archivo. Los errores de lectura se
+/// devuelven al consumir el flujo.
+/// 
+/// ## Ejemplo
+/// ```no_run
+/// # use parseit::{Decimal, Value, load_config_from_paths, read_records};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// let format_def = &schema.formats["sifere-retenciones"];
+/// let mut total = Decimal::ZERO;
+/// let records = read_records("data.dat", format_def, &schema)?;
+/// for record in records {
+///     for value in record?.values {
+///         if let Value::Decimal(amount) = value {
+///             total += amount;
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn read_records<'a>(
+    file_path: &str,
+    format_def: &'a FormatDefinition,
+    schema: &'a ConfigSchema,
+    ) -> Result<Records<'a>, Box<dyn Error>> {
+    Ok(read_records_from(DataInput::open(file_path)?, format_def, schema))
+}
+
+/// Igual que `read_records`, pero a partir de un archivo de datos ya abierto (por
+/// ejemplo, la entrada estándar o un archivo cuyo formato se dedujo de su muestra).
+/// 
+/// ## Argumentos
+/// - `input`: Archivo de datos abierto (ver `DataInput`).
+/// - `format_def`: Definición del formato seleccionado.
+/// - `schema`: Esquema de configuración cargado.
+/// 
+/// ## Retorno
+/// `Records` - Flujo de registros tipados.
+/// 
+/// ## Ejemplo
+/// ```no_run
+/// # use parseit::{DataInput, deduce_format_from, load_config_from_paths, read_records_from};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// let input = DataInput::open("-")?;
+/// let format_name = deduce_format_from(&input, &schema)?;
+/// let records = read_records_from(input, &schema.formats[&format_name], &schema);
+/// # Ok(())
+/// # }
+/// ```
+pub fn read_records_from<'a>(
+    input: DataInput,
+    format_def: &'a FormatDefinition,
+    schema: &'a ConfigSchema,
+    ) -> Records<'a> {
+    
+    let reader = input.into_reader();
+    let layouts = record_layouts(format_def);
+    let record_fields: Vec<&[FieldDefinition]> = layouts.iter().map(|(_, fields)| *fields).collect();
+
+    // Iterar por las lineas del archivo a medida que se consume el flujo
+    let records = reader.split(format_def.encoding.line_terminator()).enumerate().filter_map(move |(line_index, line_result)| {
+        
+        let buffer = match line_result {
+            Ok(buffer) => buffer,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let line_number = line_index + 1;
+
+        let Some(layout_index) = select_record_layout(format_def, &buffer) else {
+            eprintln!("Advertencia: Línea {}: No coincide con ningún tipo de registro del formato.", line_number);
+            return None;
+        };
+
+        // Procesamos cada columna
+        let values = parse_line(
+            &buffer, record_fields[layout_index], &format_def.delimiter, format_def.encoding, line_number, schema
+        );
+
+        Some(Ok(Record { record_type: layout_index, line_number, values }))
+    });
+
+    Records { layouts, records: Box::new(records) }
+}
+
+/// Procesa el archivo de datos y devuelve un flujo de registros listo para las
+/// salidas. Se construye sobre `read_records`, agregando los encabezados y anchos de
+/// cada tipo de registro y las opciones con que los valores se convierten en texto.
+/// 
 /// ## Argumentos
+/// - `input`: Archivo de datos abierto (ver `DataInput`).
+/// - `format_def`: Definición del formato seleccionado.
+/// - `schema`: Esquema de configuración cargado.
+/// - `format_numeric`: Indica si se debe aplicar formateo numérico con separadores.
+/// - `dont_use_tables`: Indica si se deben evitar las tablas de lookup.
+/// - `long_format`: Indica si se debe devolver la salida en formato largo.
+/// 
+/// ## Retorno
+/// `RecordStream` - Flujo de registros con un tipo de registro por cada layout del
+/// formato (uno solo en formato largo). Los errores de lectura se devuelven al
+/// consumir el flujo.
+/// 
+/// ## Ejemplo
+/// ```no_run
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::parse::parse_to_records;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// let format_def = &schema.formats["sifere-retenciones"];
+/// let records = parse_to_records(DataInput::open("data.dat")?, format_def, &schema, true, false, false);
+/// for record in records {
+///     println!("{:?}", record?.values);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_to_records<'a>(input: DataInput, 
+                        format_def: &'a FormatDefinition,
+                        schema: &'a ConfigSchema,
+                        format_numeric: bool,
+                        dont_use_tables: bool,
+                        long_format: bool,
+                    ) -> RecordStream<'a> {
+    
+    let records = read_records_from(input, format_def, schema);
+
+    // Un tipo de registro de salida por cada layout del formato
+    let record_types: Vec<RecordType> = records.layouts.iter()
+        .map(|(name, fields)| RecordType {
+            name: name.to_string(),
+            headers: fields.iter().map(|f| f.nombre.clone()).collect(),
+            widths: fields.iter().map(|f| column_width(f, schema, dont_use_tables)).collect(),
+            fields: fields.to_vec(),
+        })
+        .collect();
+
+    let stream = RecordStream {
+        record_types,
+        options: RenderOptions { format_numeric, dont_use_tables },
+        records: Box::new(records),
+    };
+
+    if long_format {
+        return into_long_format(stream, !format_def.records.is_empty());
+    }
+
+    stream
+}
+
+/// Transpone un flujo de registros al formato largo: cada valor se emite como una
+/// fila con número de registro, nombre de columna y valor (más el tipo de registro
+/// en los formatos multi-registro). La transposición se hace a medida que se
+/// consume el flujo.
 ///
-/// * `file_path`: La ruta al archivo de datos de longitud fija que se va a analizar.
-/// * `formats`: Un mapa de todas las definiciones de formato disponibles (`FormatDefinition`) 
-///              extraídas del archivo de configuración.
+/// ## Argumentos
+/// - `stream`: Flujo de registros a transponer.
+/// - `with_record_type`: Indica si se agrega la columna `record_type`.
 ///
 /// ## Retorno
-/// `Result<String, Box<dyn Error>>`.
-/// * **`Ok(String)`**: Contiene el nombre del formato cuya longitud de registro coincide.
-/// * **`Err(Box<dyn Error>)`**: Si no se encuentra ninguna coincidencia o si hay un error de lectura del archivo.
+/// `RecordStream` - Flujo con un único tipo de registro de tres (o cuatro) columnas.
+fn into_long_format(stream: RecordStream<'_>, with_record_type: bool) -> RecordStream<'_> {
+    let RecordStream { record_types: source_types, options, records } = stream;
+
+    let mut headers = vec!["#".to_string(), "Columna".to_string(), "Valor".to_string()];
+    // El número de registro se estima hasta 7 dígitos; los valores más largos simplemente desbordan
+    let mut widths = vec![
+        7,
+        source_types.iter().flat_map(|t| t.headers.iter().map(|h| h.chars().count())).max().unwrap_or(0),
+        source_types.iter().flat_map(|t| t.widths.iter().copied()).max().unwrap_or(0),
+    ];
+    if with_record_type {
+        headers.insert(1, "record_type".to_string());
+        widths.insert(1, source_types.iter().map(|t| t.name.chars().count()).max().unwrap_or(0));
+    }
+    let widths: Vec<usize> = widths.into_iter().zip(headers.iter()).map(|(w, h)| w.max(h.chars().count())).collect();
+
+    // Las columnas del formato largo son de texto: los valores ya se emiten formateados
+    let fields = headers.iter().zip(widths.iter())
+        .map(|(header, width)| FieldDefinition {
+            nombre: header.clone(),
+            len: *width,
+            tipo: "string".to_string(),
+            param1: String::new(),
+            param2: String::new(),
+        })
+        .collect();
+
+    let flat_records = records.enumerate().flat_map(move |(row_index, record)| {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => return vec![Err(e)],
+        };
+
+        let row_num = (row_index + 1).to_string();
+        let record_type = &source_types[record.record_type];
+
+        record_type.render(&record.values, options).into_iter().enumerate().map(|(col_index, value)| {
+            let col_name = record_type.headers.get(col_index).cloned().unwrap_or_else(|| format!("col_{}", col_index + 1));
+            let mut values = vec![Value::Text(row_num.clone()), Value::Text(col_name), Value::Text(value)];
+            if with_record_type {
+                values.insert(1, Value::Text(record_type.name.clone()));
+            }
+            Ok(Record { record_type: 0, line_number: record.line_number, values })
+        }).collect()
+    });
+
+    RecordStream {
+        record_types: vec![RecordType { name: String::new(), headers, widths, fields }],
+        options,
+        records: Box::new(flat_records),
+    }
+}
+
+/// Nombre de la columna con el archivo de origen de cada registro en la salida concatenada.
+pub const SOURCE_FILE_COLUMN: &str = "source_file";
+
+/// Nombre de la columna con el número de línea de cada registro en la salida concatenada.
+pub const LINE_COLUMN: &str = "line";
+
+/// Concatena los flujos de registros de varios archivos de un mismo formato en un único
+/// flujo, agregando al comienzo de cada registro las columnas `source_file` (archivo de
+/// origen) y `line` (número de línea en ese archivo). Los archivos se recorren uno detrás
+/// de otro a medida que se consume el flujo.
 ///
-/// ## Errores
+/// ## Argumentos
+/// - `streams`: Ruta de cada archivo de datos y su flujo de registros. Todos deben
+///   provenir del mismo formato y de las mismas opciones (se usan los tipos de registro
+///   y las opciones del primero).
 ///
-/// Retorna un error si:
-/// * No se puede abrir o leer la primera línea del archivo (`file_path`).
-/// * Ninguna `FormatDefinition` en `formats` coincide con la longitud del primer registro.
+/// ## Retorno
+/// `RecordStream` - Flujo con los tipos de registro del formato y las dos columnas agregadas.
 ///
 /// ## Ejemplo
-///
-/// ```ignore
-/// // Asumiendo que 'config_schema' ya está cargado y 'file_path' es válido.
-/// let formats = &config_schema.formats;
-/// match deduce_format("data.dat", formats) {
-///     Ok(name) => println!("Formato deducido: {}", name),
-///     Err(e) => eprintln!("Fallo al deducir el formato: {}", e),
-/// }
+/// ```no_run
+/// # use parseit::{DataInput, load_config_from_paths};
+/// # use parseit::parse::{concat_record_streams, parse_to_records};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// let format_def = &schema.formats["sifere-retenciones"];
+/// let streams = vec![
+///     ("enero.txt".to_string(), parse_to_records(DataInput::open("enero.txt")?, format_def, &schema, false, false, false)),
+///     ("febrero.txt".to_string(), parse_to_records(DataInput::open("febrero.txt")?, format_def, &schema, false, false, false)),
+/// ];
+/// let records = concat_record_streams(streams);
+/// # Ok(())
+/// # }
 /// ```
-pub fn deduce_format(
-    file_path: &str, 
-    formats: &HashMap<String, FormatDefinition>
-    ) -> Result<String, Box<dyn Error>> {
-    let data_len = get_first_line_length(file_path)?;
-    
-    for (name, definition) in formats.iter() {
-        let format_len = calculate_format_length(&definition.fields);
+pub fn concat_record_streams(streams: Vec<(String, RecordStream<'_>)>) -> RecordStream<'_> {
+    let source_width = streams.iter().map(|(source, _)| source.chars().count()).max().unwrap_or(0);
+    let mut streams = streams.into_iter().peekable();
+
+    let (mut record_types, options) = match streams.peek_mut() {
+        Some((_, stream)) => (std::mem::take(&mut stream.record_types), stream.options),
+        None => (Vec::new(), RenderOptions::default()),
+    };
 
-        if data_len == format_len {
-            return Ok(name.clone());
+    // El número de línea se estima hasta 7 dígitos, como el número de registro del formato largo
+    let source_columns = [(SOURCE_FILE_COLUMN, source_width), (LINE_COLUMN, 7)];
+    for record_type in record_types.iter_mut() {
+        for (index, (name, width)) in source_columns.iter().enumerate() {
+            let width = (*width).max(name.len());
+            record_type.headers.insert(index, name.to_string());
+            record_type.widths.insert(index, width);
+            record_type.fields.insert(index, FieldDefinition {
+                nombre: name.to_string(),
+                len: width,
+                tipo: "string".to_string(),
+                param1: String::new(),
+                param2: String::new(),
+            });
         }
     }
 
-    Err(format!(
-        "No se pudo identificar el formato. Ningún formato coincide con la longitud de registro de {} bytes.",
-        data_len
-    ).into())
-}
\ No newline at end of file
+    let records = streams.flat_map(|(source, stream)| {
+        stream.map(move |record| {
+            let mut record = record?;
+            record.values.splice(0..0, [Value::Text(source.clone()), Value::Text(record.line_number.to_string())]);
+            Ok(record)
+        })
+    });
+
+    RecordStream { record_types, options, records: Box::new(records) }
+}
+
+/// Cantidad de líneas del archivo que se usan como muestra para deducir el formato.
+pub const DETECTION_SAMPLE_LINES: usize = 20;
+
+/// Resultado de evaluar un formato contra una muestra de líneas del archivo.
+/// - name: Nombre del formato.
+/// - sample_lines: Líneas de la muestra (separadas según la codificación del formato).
+/// - matched_lines: Líneas cuya longitud (o cantidad de campos) coincide con el formato.
+/// - valid_lines: Líneas que además superan todas las validaciones de contenido.
+/// - passed_checks: Validaciones de contenido superadas (tablas, fechas, números).
+/// - failed_checks: Validaciones de contenido fallidas.
+/// - rejection: Motivo por el que se descartó el formato (`None` si es candidato).
+#[derive(Debug)]
+pub struct FormatScore {
+    pub name: String,
+    pub sample_lines: usize,
+    pub matched_lines: usize,
+    pub valid_lines: usize,
+    pub passed_checks: usize,
+    pub failed_checks: usize,
+    pub rejection: Option<String>,
+}
+
+impl FormatScore {
+    /// Puntaje del formato: validaciones superadas menos validaciones fallidas.
+    pub fn score(&self) -> i64 {
+        self.passed_checks as i64 - self.failed_checks as i64
+    }
+}
+
+/// Valida el contenido de un campo según su tipo, para puntuar un formato candidato.
+///
+/// ## Argumentos
+/// - `field`: Definición del campo.
+/// - `raw_value`: Valor crudo, ya recortado.
+/// - `schema`: Esquema de configuración cargado (tablas de lookup).
+///
+/// ## Retorno
+/// `Option<bool>` - `Some(true)` si el valor es válido para el tipo, `Some(false)` si no,
+/// o `None` si el campo no aporta información (texto libre, valores vacíos o tablas inexistentes).
+fn check_field_value(field: &FieldDefinition, raw_value: &str, schema: &ConfigSchema) -> Option<bool> {
+    if raw_value.is_empty() {
+        return None;
+    }
+
+    match field.tipo.as_str() {
+        "table" => schema.tables.get(&field.param1).map(|table| table.contains_key(raw_value)),
+        "date" => Some(parse_date_value(raw_value, &field.param1).is_ok()),
+        "zamount" => Some(raw_value.chars().all(|c| c.is_ascii_digit())),
+        "amount" => Some(
+            raw_value.chars().any(|c| c.is_ascii_digit())
+                && raw_value.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-'))
+        ),
+        _ => None,
+    }
+}
+
+/// Evalúa un formato contra una muestra de líneas del archivo.
+///
+/// Un formato es candidato si todas las líneas de la muestra coinciden en longitud
+/// (o en cantidad de campos, para los formatos delimitados) con alguno de sus tipos
+/// de registro y es válida en la codificación del formato. Una línea de longitud fija
+/// puede tener espacios después del registro, pero no puede ser más corta que él.
+/// Luego se valida el contenido de cada campo con `check_field_value`.
+///
+/// ## Argumentos
+/// - `name`: Nombre del formato.
+/// - `definition`: Definición del formato.
+/// - `sample`: Líneas de muestra (bytes crudos, sin el salto de línea final), separadas
+///   según la codificación del formato (ver `DataInput::sample_lines`).
+/// - `schema`: Esquema de configuración cargado.
+///
+/// ## Retorno
+/// `FormatScore` - Resultado de la evaluación.
+pub fn score_format(
+    name: &str,
+    definition: &FormatDefinition,
+    sample: &[Vec<u8>],
+    schema: &ConfigSchema,
+    ) -> FormatScore {
+
+    let layouts = record_layouts(definition);
+    let mut result = FormatScore {
+        name: name.to_string(),
+        sample_lines: sample.len(),
+        matched_lines: 0,
+        valid_lines: 0,
+        passed_checks: 0,
+        failed_checks: 0,
+        rejection: None,
+    };
+
+    for (line_index, line) in sample.iter().enumerate() {
+        let Some(layout_index) = select_record_layout(definition, line) else {
+            result.rejection.get_or_insert_with(|| format!(
+                "la línea {} no coincide con ningún tipo de registro", line_index + 1
+            ));
+            continue;
+        };
+        let (_, fields) = layouts[layout_index];
+        let encoding = definition.encoding;
+
+        if let Err(offset) = encoding.decode(line) {
+            result.rejection.get_or_insert_with(|| format!(
+                "la línea {} tiene bytes no válidos en {} (posición {})", line_index + 1, encoding, offset + 1
+            ));
+            continue;
+        }
+
+        // 1. Extraer los valores crudos, verificando la estructura de la línea
+        let values: Vec<String> = if definition.delimiter.is_empty() {
+            let format_len = calculate_format_length(fields);
+            let trimmed_len = encoding.trim_end(line).len();
+
+            // Se admite que el proveedor haya dejado o no espacios después del registro, pero
+            // una línea más corta no se podría interpretar (ver `parse_line`)
+            if trimmed_len > format_len || line.len() < format_len {
+                result.rejection.get_or_insert_with(|| format!(
+                    "la línea {} mide {} bytes y el formato {}", line_index + 1, trimmed_len, format_len
+                ));
+                continue;
+            }
+
+            let mut start_pos = 0;
+            fields.iter().map(|field| {
+                // Un carácter multibyte partido entre dos campos no invalida la línea
+                let cow = encoding.decode_lossy(&line[start_pos..start_pos + field.len]);
+                start_pos += field.len;
+                cow.trim().to_string()
+            }).collect()
+        } else {
+            let decoded = encoding.decode_lossy(line);
+            let values = split_delimited_line(&decoded, &definition.delimiter);
+
+            if values.len() != fields.len() {
+                result.rejection.get_or_insert_with(|| format!(
+                    "la línea {} tiene {} campos y el formato {}", line_index + 1, values.len(), fields.len()
+                ));
+                continue;
+            }
+
+            values.iter().map(|v| v.trim().to_string()).collect()
+        };
+
+        result.matched_lines += 1;
+
+        // 2. Validar el contenido de cada campo
+        let mut line_is_valid = true;
+        for (field, value) in fields.iter().zip(values.iter()) {
+            match check_field_value(field, value, schema) {
+                Some(true) => result.passed_checks += 1,
+                Some(false) => {
+                    result.failed_checks += 1;
+                    line_is_valid = false;
+                }
+                None => {}
+            }
+        }
+        if line_is_valid {
+            result.valid_lines += 1;
+        }
+    }
+
+    result
+}
+
+/// Evalúa todos los formatos del esquema contra una muestra de líneas del archivo.
+/// Cada formato se evalúa con las líneas separadas según su codificación.
+///
+/// ## Argumentos
+/// - `input`: Archivo de datos abierto (ver `DataInput::sample_lines`).
+/// - `schema`: Esquema de configuración cargado.
+///
+/// ## Retorno
+/// `Vec<FormatScore>` - Un resultado por formato, ordenados por nombre.
+pub fn score_formats(input: &DataInput, schema: &ConfigSchema) -> Vec<FormatScore> {
+    let mut scores: Vec<FormatScore> = schema.formats.iter()
+        .map(|(name, definition)| score_format(name, definition, &input.sample_lines(definition.encoding), schema))
+        .collect();
+    scores.sort_by(|a, b| a.name.cmp(&b.name));
+    scores
+}
+
+/// Devuelve los formatos candidatos (no descartados) con el puntaje máximo.
+/// Si hay más de uno, la deducción es ambigua.
+///
+/// ## Argumentos
+/// - `scores`: Resultados de `score_formats`.
+///
+/// ## Retorno
+/// `Vec<&FormatScore>` - Candidatos con el mejor puntaje (vacío si no hay candidatos).
+pub fn best_candidates(scores: &[FormatScore]) -> Vec<&FormatScore> {
+    let candidates = scores.iter().filter(|s| s.rejection.is_none());
+    let Some(best_score) = candidates.clone().map(|s| s.score()).max() else {
+        return Vec::new();
+    };
+    candidates.filter(|s| s.score() == best_score).collect()
+}
+
+/// Intenta identificar el formato de un archivo de datos evaluando una muestra de
+/// sus primeras líneas (`DETECTION_SAMPLE_LINES`) contra cada formato del esquema.
+///
+/// Cada formato cuya longitud de registro (o cantidad de campos, en los formatos
+/// delimitados) coincide con todas las líneas de la muestra es candidato. Los
+/// candidatos se puntúan validando el contenido de sus campos: códigos presentes
+/// en las tablas, fechas que respetan el patrón de `param1` y montos numéricos.
+/// Gana el candidato de mayor puntaje; si hay un empate se informa la ambigüedad,
+/// salvo que los empatados sean versiones de un mismo formato: en ese caso gana la
+/// vigente en la fecha del archivo (ver `select_format_version`).
+///
+/// ## Argumentos
+///
+/// * `file_path`: La ruta al archivo de datos que se va a analizar.
+/// * `schema`: Esquema de configuración cargado (formatos y tablas).
+///
+/// ## Retorno
+/// `Result<String, Box<dyn Error>>`.
+/// * **`Ok(String)`**: Contiene el nombre del formato con mayor puntaje.
+/// * **`Err(Box<dyn Error>)`**: Si no hay candidatos, si hay un empate o si hay un error de lectura del archivo.
+///
+/// ## Errores
+///
+/// Retorna un error si:
+/// * No se puede abrir o leer el archivo (`file_path`).
+/// * Ningún formato coincide con la longitud de los registros de la muestra.
+/// * Más de un formato obtiene el puntaje máximo.
+///
+/// ## Ejemplo
+///
+/// ```no_run
+/// # use parseit::{deduce_format, load_config_from_paths};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config_schema = load_config_from_paths()?;
+/// match deduce_format("data.dat", &config_schema) {
+///     Ok(name) => println!("Formato deducido: {}", name),
+///     Err(e) => eprintln!("Fallo al deducir el formato: {}", e),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn deduce_format(
+    file_path: &str, 
+    schema: &ConfigSchema,
+    ) -> Result<String, Box<dyn Error>> {
+    deduce_format_from(&DataInput::open(file_path)?, schema)
+}
+
+/// Codificación con la que se miden las líneas de un archivo cuyo formato no se conoce
+/// (en los mensajes y reportes de la deducción): la de todos los formatos si es la misma
+/// (por ejemplo, con `--encoding`) o windows-1252 si difieren.
+///
+/// ## Argumentos
+/// - `schema`: Esquema de configuración cargado.
+///
+/// ## Retorno
+/// `Encoding` - Codificación común a los formatos o la codificación por defecto.
+pub fn detection_encoding(schema: &ConfigSchema) -> Encoding {
+    let mut encodings = schema.formats.values().map(|f| f.encoding);
+    match encodings.next() {
+        Some(first) if encodings.all(|e| e == first) => first,
+        _ => Encoding::default(),
+    }
+}
+
+/// Igual que `deduce_format`, pero a partir de la muestra de líneas de un archivo ya
+/// abierto (`DataInput::sample_lines`), sin volver a leerlo.
+///
+/// ## Argumentos
+/// - `input`: Archivo de datos abierto.
+/// - `schema`: Esquema de configuración cargado (formatos y tablas).
+///
+/// ## Retorno
+/// `Result<String, Box<dyn Error>>` - Nombre del formato con mayor puntaje.
+///
+/// ## Errores
+/// Retorna un error si la muestra está vacía, si ningún formato coincide o si hay un empate.
+///
+/// ## Ejemplo
+/// ```no_run
+/// # use parseit::{DataInput, deduce_format_from, load_config_from_paths};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// let input = DataInput::open("-")?;
+/// let format_name = deduce_format_from(&input, &schema)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn deduce_format_from(
+    input: &DataInput,
+    schema: &ConfigSchema,
+    ) -> Result<String, Box<dyn Error>> {
+
+    let encoding = detection_encoding(schema);
+    let sample = input.sample_lines(encoding);
+    let Some(first_line) = sample.first() else {
+        return Err("No se pudo identificar el formato. El archivo está vacío.".into());
+    };
+
+    let scores = score_formats(input, schema);
+    let best = best_candidates(&scores);
+
+    match best.as_slice() {
+        [] => Err(format!(
+            "No se pudo identificar el formato. Ningún formato coincide con la longitud de registro de {} bytes. \
+             Use --explain-detection para ver el detalle.",
+            encoding.trim_end(first_line).len()
+        ).into()),
+        [format] => Ok(format.name.clone()),
+        _ => {
+            let names: Vec<&str> = best.iter().map(|s| s.name.as_str()).collect();
+
+            // Las versiones de un mismo formato con igual puntaje se distinguen por la fecha
+            let (base_name, _) = split_format_name(names[0]);
+            if names.iter().all(|name| split_format_name(name).0 == base_name && split_format_name(name).1.is_some()) {
+                // `best` está ordenado por nombre; las versiones se prueban por inicio de vigencia
+                let versions: Vec<&str> = format_versions(schema, base_name).into_iter()
+                    .filter(|version| names.contains(version))
+                    .collect();
+                return effective_version(input, schema, base_name, &versions)
+                    .map_err(|e| format!("No se pudo identificar la versión del formato. {}", e).into());
+            }
+
+            Err(format!(
+                "No se pudo identificar el formato. Hay {} formatos con el mismo puntaje ({}): {}. Indique uno con --format-name.",
+                best.len(), best[0].score(), names.join(", ")
+            ).into())
+        }
+    }
+}
+
+/// Lee la fecha del campo `date_field` de una línea, con el diseño del formato.
+///
+/// ## Argumentos
+/// - `definition`: Definición del formato.
+/// - `line`: Bytes crudos de la línea, sin el salto de línea final.
+///
+/// ## Retorno
+/// `Option<NaiveDate>` - La fecha, o `None` si el formato no declara `date_field`, la
+/// línea no lo contiene o su valor no es una fecha.
+fn line_date(definition: &FormatDefinition, line: &[u8]) -> Option<NaiveDate> {
+    let layout_index = select_record_layout(definition, line)?;
+    let (_, fields) = record_layouts(definition)[layout_index];
+    let index = fields.iter().position(|f| f.nombre == definition.date_field)?;
+    let field = &fields[index];
+
+    let raw_value = if definition.delimiter.is_empty() {
+        let start_pos: usize = fields[..index].iter().map(|f| f.len).sum();
+        definition.encoding.decode_lossy(line.get(start_pos..start_pos + field.len)?).into_owned()
+    } else {
+        let decoded = definition.encoding.decode_lossy(line);
+        split_delimited_line(&decoded, &definition.delimiter).into_iter().nth(index)?
+    };
+
+    parse_date_value(&raw_value, &field.param1).ok().flatten()
+}
+
+/// Elige, entre las versiones indicadas de un formato, la vigente en la fecha del
+/// archivo. Se prueban de la más reciente a la más antigua: para cada versión cuyo
+/// diseño coincide con la muestra se lee su `date_field` de la primera línea que lo
+/// informa, y gana la primera vigente en esa fecha.
+///
+/// ## Argumentos
+/// - `input`: Archivo de datos abierto.
+/// - `schema`: Esquema de configuración cargado.
+/// - `base_name`: Nombre del formato, sin versión (para los mensajes).
+/// - `versions`: Versiones candidatas, ordenadas por inicio de vigencia.
+///
+/// ## Retorno
+/// `Result<String, String>` - Nombre de la versión o descripción del motivo por el que
+/// no se pudo elegir.
+fn effective_version(input: &DataInput, schema: &ConfigSchema, base_name: &str, versions: &[&str]) -> Result<String, String> {
+    let mut dates: Vec<NaiveDate> = Vec::new();
+
+    for name in versions.iter().rev() {
+        let definition = &schema.formats[*name];
+        if definition.date_field.is_empty() {
+            continue;
+        }
+
+        let sample = input.sample_lines(definition.encoding);
+        if score_format(name, definition, &sample, schema).rejection.is_some() {
+            continue;
+        }
+        let Some(date) = sample.iter().find_map(|line| line_date(definition, line)) else {
+            continue;
+        };
+
+        if definition.is_effective(date) {
+            return Ok(name.to_string());
+        }
+        dates.push(date);
+    }
+
+    let listed: Vec<String> = versions.iter()
+        .map(|name| format!("{} ({})", name, schema.formats[*name].validity()))
+        .collect();
+    match dates.first() {
+        None => Err(format!(
+            "No se pudo leer la fecha del archivo con ninguna versión de '{}' que coincida con sus registros: {}. \
+             Indique una con -f {}{}versión.",
+            base_name, listed.join(", "), base_name, VERSION_SEPARATOR
+        )),
+        Some(date) => Err(format!(
+            "Ninguna versión de '{}' está vigente el {}: {}. Indique una con -f {}{}versión.",
+            base_name, date, listed.join(", "), base_name, VERSION_SEPARATOR
+        )),
+    }
+}
+
+/// Elige la versión de un formato que corresponde a un archivo de datos, según la fecha
+/// de su `date_field` y la vigencia (`valid_from` y `valid_to`) de cada versión.
+///
+/// ## Argumentos
+/// - `input`: Archivo de datos abierto.
+/// - `schema`: Esquema de configuración cargado.
+/// - `base_name`: Nombre del formato, sin versión (ej: "compras-comprobantes").
+///
+/// ## Retorno
+/// `Result<String, Box<dyn Error>>` - Nombre completo de la versión elegida
+/// (ej: "compras-comprobantes@rg3685-v2").
+///
+/// ## Errores
+/// Retorna un error si el formato no tiene versiones, si no se puede leer la fecha del
+/// archivo o si ninguna versión está vigente en esa fecha.
+///
+/// ## Ejemplo
+/// ```no_run
+/// # use parseit::{DataInput, load_config_from_paths, select_format_version};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// let input = DataInput::open("compras_2024_03.txt")?;
+/// let format_name = select_format_version(&input, &schema, "compras-comprobantes")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn select_format_version(
+    input: &DataInput,
+    schema: &ConfigSchema,
+    base_name: &str,
+    ) -> Result<String, Box<dyn Error>> {
+
+    let versions = format_versions(schema, base_name);
+    if versions.is_empty() {
+        return Err(format!("El formato '{}' no se encontró en {}", base_name, CONFIG_FILE).into());
+    }
+
+    Ok(effective_version(input, schema, base_name, &versions)?)
+}
+
+/// Error encontrado al validar un archivo de datos contra un formato.
+/// - line: Número de línea (desde 1).
+/// - offset: Posición de inicio del campo (desde 1). En los formatos delimitados
+///   es el número de campo.
+/// - field: Nombre del campo (vacío si el error es de la línea completa).
+/// - rule: Regla incumplida: `longitud`, `tipo_de_registro`, `cantidad_de_campos`,
+///   `ancho_maximo`, `numerico`, `fecha`, `tabla` o `codificacion` (bytes no válidos en
+///   la codificación del formato; el offset es el del primer byte inválido).
+/// - value: Valor crudo del campo, o el detalle del error de la línea.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub line: usize,
+    pub offset: usize,
+    pub field: String,
+    pub rule: &'static str,
+    pub value: String,
+}
+
+/// Valida el valor de un campo según su tipo.
+///
+/// ## Argumentos
+/// - `field`: Definición del campo.
+/// - `raw_value`: Valor crudo, ya recortado.
+/// - `schema`: Esquema de configuración cargado (tablas de lookup).
+///
+/// ## Retorno
+/// `Option<&'static str>` - La regla incumplida, o `None` si el valor es válido.
+/// Los montos `zamount` deben venir completos con dígitos (un valor vacío es un error).
+fn validate_field_value(field: &FieldDefinition, raw_value: &str, schema: &ConfigSchema) -> Option<&'static str> {
+    if field.tipo == "zamount" && raw_value.is_empty() {
+        return Some("numerico");
+    }
+
+    match check_field_value(field, raw_value, schema) {
+        Some(false) => match field.tipo.as_str() {
+            "table" => Some("tabla"),
+            "date" => Some("fecha"),
+            _ => Some("numerico"),
+        },
+        _ => None,
+    }
+}
+
+/// Valida cada línea del archivo de datos contra las definiciones de campos del formato:
+/// longitud exacta del registro (o cantidad de campos y ancho máximo, en los formatos
+/// delimitados), montos `zamount` sólo numéricos, fechas que respetan el patrón de
+/// `param1`, códigos presentes en las tablas de `schema.tables` y bytes válidos en la
+/// codificación del formato.
+///
+/// Las líneas se procesan en flujo y cada error se entrega a `report` a medida que se encuentra.
+///
+/// ## Argumentos
+/// - `file_path`: Ruta al archivo de datos.
+/// - `format_def`: Definición del formato a validar.
+/// - `schema`: Esquema de configuración cargado.
+/// - `report`: Función que recibe cada error encontrado.
+///
+/// ## Retorno
+/// `Result<usize, Box<dyn Error>>` - Cantidad de errores encontrados.
+///
+/// ## Errores
+/// Retorna un error si no se puede abrir o leer el archivo, o si `report` falla.
+///
+/// ## Ejemplo
+/// ```no_run
+/// # use parseit::{load_config_from_paths, validate_file};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = load_config_from_paths()?;
+/// let format_def = &schema.formats["sifere-retenciones"];
+/// let errors = validate_file("data.dat", format_def, &schema, |e| {
+///     println!("{}: {} ({})", e.line, e.field, e.rule);
+///     Ok(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn validate_file(
+    file_path: &str,
+    format_def: &FormatDefinition,
+    schema: &ConfigSchema,
+    report: impl FnMut(&ValidationError) -> Result<(), Box<dyn Error>>,
+    ) -> Result<usize, Box<dyn Error>> {
+    validate_file_from(DataInput::open(file_path)?, format_def, schema, report)
+}
+
+/// Igual que `validate_file`, pero a partir de un archivo de datos ya abierto (ver `DataInput`).
+///
+/// ## Argumentos
+/// - `input`: Archivo de datos abierto.
+/// - `format_def`: Definición del formato a validar.
+/// - `schema`: Esquema de configuración cargado.
+/// - `report`: Función que recibe cada error encontrado.
+///
+/// ## Retorno
+/// `Result<usize, Box<dyn Error>>` - Cantidad de errores encontrados.
+///
+/// ## Errores
+/// Retorna un error si no se puede leer el archivo, o si `report` falla.
+pub fn validate_file_from(
+    input: DataInput,
+    format_def: &FormatDefinition,
+    schema: &ConfigSchema,
+    mut report: impl FnMut(&ValidationError) -> Result<(), Box<dyn Error>>,
+    ) -> Result<usize, Box<dyn Error>> {
+
+    let reader = input.into_reader();
+    let layouts = record_layouts(format_def);
+    let encoding = format_def.encoding;
+    let mut error_count = 0;
+
+    let mut emit = |error: ValidationError| -> Result<(), Box<dyn Error>> {
+        error_count += 1;
+        report(&error)
+    };
+
+    for (line_index, line_result) in reader.split(encoding.line_terminator()).enumerate() {
+        let mut buffer = line_result?;
+        let line_number = line_index + 1;
+
+        if buffer.last() == Some(&b'\r') {
+            buffer.pop();
+        }
+
+        let Some(layout_index) = select_record_layout(format_def, &buffer) else {
+            emit(ValidationError {
+                line: line_number,
+                offset: 1,
+                field: String::new(),
+                rule: "tipo_de_registro",
+                value: encoding.decode_lossy(&buffer).into_owned(),
+            })?;
+            continue;
+        };
+        let (_, fields) = layouts[layout_index];
+
+        if format_def.delimiter.is_empty() {
+            // 1. Longitud exacta del registro
+            let format_len = calculate_format_length(fields);
+            if buffer.len() != format_len {
+                emit(ValidationError {
+                    line: line_number,
+                    offset: 1,
+                    field: String::new(),
+                    rule: "longitud",
+                    value: format!("{} bytes, se esperaban {}", buffer.len(), format_len),
+                })?;
+            }
+
+            // 2. Contenido de cada campo completo
+            let mut start_pos = 0;
+            for field in fields.iter() {
+                let end_pos = start_pos + field.len;
+                if end_pos > buffer.len() {
+                    break;
+                }
+
+                let bytes = &buffer[start_pos..end_pos];
+                let error = match encoding.decode(bytes) {
+                    Ok(raw_value) => validate_field_value(field, raw_value.trim(), schema)
+                        .map(|rule| (rule, start_pos, raw_value)),
+                    Err(offset) => Some(("codificacion", start_pos + offset, encoding.decode_lossy(bytes))),
+                };
+
+                if let Some((rule, position, raw_value)) = error {
+                    emit(ValidationError {
+                        line: line_number,
+                        offset: position + 1,
+                        field: field.nombre.clone(),
+                        rule,
+                        value: raw_value.into_owned(),
+                    })?;
+                }
+                start_pos = end_pos;
+            }
+        } else {
+            if let Err(offset) = encoding.decode(&buffer) {
+                emit(ValidationError {
+                    line: line_number,
+                    offset: offset + 1,
+                    field: String::new(),
+                    rule: "codificacion",
+                    value: encoding.decode_lossy(&buffer).into_owned(),
+                })?;
+            }
+
+            let line = encoding.decode_lossy(&buffer);
+            let values = split_delimited_line(&line, &format_def.delimiter);
+
+            // 1. Cantidad de campos
+            if values.len() != fields.len() {
+                emit(ValidationError {
+                    line: line_number,
+                    offset: 1,
+                    field: String::new(),
+                    rule: "cantidad_de_campos",
+                    value: format!("{} campos, se esperaban {}", values.len(), fields.len()),
+                })?;
+            }
+
+            // 2. Ancho máximo y contenido de cada campo
+            for (field_index, (field, value)) in fields.iter().zip(values.iter()).enumerate() {
+                let raw_value = value.trim();
+                let rule = if raw_value.chars().count() > field.len {
+                    Some("ancho_maximo")
+                } else {
+                    validate_field_value(field, raw_value, schema)
+                };
+
+                if let Some(rule) = rule {
+                    emit(ValidationError {
+                        line: line_number,
+                        offset: field_index + 1,
+                        field: field.nombre.clone(),
+                        rule,
+                        value: value.clone(),
+                    })?;
+                }
+            }
+        }
+    }
+
+    Ok(error_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Esquema de prueba a partir del contenido de un `parseit.toon`, ya resuelto.
+    fn schema_from(content: &str) -> ConfigSchema {
+        let mut schema: ConfigSchema = toon_format::decode_default(content).unwrap();
+        schema.resolve().unwrap();
+        schema
+    }
+
+    /// Archivo de datos de prueba en memoria.
+    fn input_from(content: impl AsRef<[u8]>) -> DataInput {
+        DataInput::from_reader("prueba.txt", Box::new(std::io::Cursor::new(content.as_ref().to_vec()))).unwrap()
+    }
+
+    /// Valores de cada registro de un archivo de datos en memoria.
+    fn values_of(schema: &ConfigSchema, format: &str, content: impl AsRef<[u8]>) -> Vec<Vec<Value>> {
+        read_records_from(input_from(content), &schema.formats[format], schema)
+            .map(|record| record.unwrap().values)
+            .collect()
+    }
+
+    const FIXED: &str = r#"formats:
+  importes:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Fecha",8,date,"%Y%m%d",""
+      "Detalle",10,string,"",""
+  importes-bis:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Fecha",8,date,"%Y%m%d",""
+      "Nota",10,string,"",""
+  codigos:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Código",2,table,provincias,""
+      "Importe",16,zamount,"2",""
+tables:
+  provincias:
+    "01": Buenos Aires
+"#;
+
+    fn score<'s>(scores: &'s [FormatScore], name: &str) -> &'s FormatScore {
+        scores.iter().find(|s| s.name == name).unwrap()
+    }
+
+    #[test]
+    fn score_format_accepts_trailing_spaces_after_the_record() {
+        let schema = schema_from(FIXED);
+        let sample = vec![b"20240501abc       ".to_vec(), b"20240502abc          ".to_vec()];
+        let result = score_format("importes", &schema.formats["importes"], &sample, &schema);
+        assert_eq!(result.rejection, None);
+        assert_eq!(result.matched_lines, 2);
+        assert_eq!(result.valid_lines, 2);
+    }
+
+    #[test]
+    fn score_format_rejects_lines_shorter_than_the_format() {
+        let schema = schema_from(FIXED);
+        let sample = vec![b"20240501abc".to_vec()];
+        let result = score_format("importes", &schema.formats["importes"], &sample, &schema);
+        assert_eq!(result.rejection.as_deref(), Some("la línea 1 mide 11 bytes y el formato 18"));
+    }
+
+    #[test]
+    fn deduce_format_ignores_formats_longer_than_the_lines() {
+        let schema = schema_from(r#"formats:
+  corto:
+    category: Pruebas
+    delimiter: ""
+    fields[1]{nombre,len,tipo,param1,param2}:
+      "Nombre",10,string,"",""
+  largo:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Nombre",10,string,"",""
+      "Apellido",10,string,"",""
+"#);
+        assert_eq!(deduce_format_from(&input_from("Juan Perez\nAna Gomez \n"), &schema).unwrap(), "corto");
+    }
+
+    #[test]
+    fn files_with_trailing_spaces_are_detected_and_parsed_with_every_field() {
+        let schema = schema_from(FIXED);
+        let content = "010000000000012345   \n010000000000000100\n";
+        assert_eq!(deduce_format_from(&input_from(content), &schema).unwrap(), "codigos");
+
+        let values = values_of(&schema, "codigos", content);
+        let amounts: Vec<&Value> = values.iter().map(|record| &record[1]).collect();
+        assert_eq!(amounts, [&Value::Decimal(Decimal::new(12345, 2)), &Value::Decimal(Decimal::new(100, 2))]);
+    }
+
+    #[test]
+    fn score_format_rejects_lines_longer_than_the_format() {
+        let schema = schema_from(FIXED);
+        let sample = vec![b"20240501abcdefghijk".to_vec()];
+        let result = score_format("importes", &schema.formats["importes"], &sample, &schema);
+        assert_eq!(result.rejection.as_deref(), Some("la línea 1 mide 19 bytes y el formato 18"));
+    }
+
+    #[test]
+    fn score_formats_prefers_the_format_whose_content_validates() {
+        let schema = schema_from(FIXED);
+        let scores = score_formats(&input_from("010000000000012345\n"), &schema);
+        assert_eq!(score(&scores, "codigos").score(), 2);
+        assert_eq!(score(&scores, "importes").score(), -1);
+        let best: Vec<&str> = best_candidates(&scores).iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(best, ["codigos"]);
+    }
+
+    #[test]
+    fn deduce_format_reports_ties() {
+        let schema = schema_from(FIXED);
+        let error = deduce_format_from(&input_from("20240501texto     \n"), &schema).unwrap_err().to_string();
+        assert!(error.contains("Hay 2 formatos con el mismo puntaje (1): importes, importes-bis"), "{}", error);
+    }
+
+    #[test]
+    fn deduce_format_reports_empty_files() {
+        let schema = schema_from(FIXED);
+        assert!(deduce_format_from(&input_from("\n  \n"), &schema).unwrap_err().to_string().contains("vacío"));
+    }
+
+    // Versiones superpuestas cuyos nombres se ordenan al revés que sus fechas (`v10` < `v9`)
+    const VERSIONED: &str = r#"formats:
+  libro@v9:
+    category: Pruebas
+    delimiter: ""
+    date_field: Fecha
+    valid_from: "2023-01-01"
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Fecha",8,date,"%Y%m%d",""
+      "Importe",10,zamount,"2",""
+  libro@v10:
+    extends: libro@v9
+    valid_from: "2024-01-01"
+"#;
+
+    #[test]
+    fn deduce_format_prefers_the_most_recent_effective_version() {
+        let schema = schema_from(VERSIONED);
+        let input = input_from("202405010000012345\n202405020000000100\n");
+        assert_eq!(deduce_format_from(&input, &schema).unwrap(), "libro@v10");
+    }
+
+    #[test]
+    fn deduce_format_uses_the_older_version_before_the_newer_starts() {
+        let schema = schema_from(VERSIONED);
+        let input = input_from("202306010000012345\n");
+        assert_eq!(deduce_format_from(&input, &schema).unwrap(), "libro@v9");
+    }
+
+    #[test]
+    fn select_format_version_by_file_date() {
+        let schema = schema_from(VERSIONED);
+        assert_eq!(select_format_version(&input_from("202405010000012345\n"), &schema, "libro").unwrap(), "libro@v10");
+        assert_eq!(select_format_version(&input_from("202306010000012345\n"), &schema, "libro").unwrap(), "libro@v9");
+        assert!(select_format_version(&input_from("202201010000012345\n"), &schema, "libro").is_err());
+        assert!(select_format_version(&input_from("202405010000012345\n"), &schema, "otro").is_err());
+    }
+
+    #[test]
+    fn parse_amount_value_applies_implicit_decimals() {
+        assert_eq!(parse_amount_value("00012345", "zamount", 2).unwrap().to_string(), "123.45");
+        assert_eq!(parse_amount_value("5", "zamount", 2).unwrap().to_string(), "0.05");
+        assert_eq!(parse_amount_value("1.234,56", "amount", 2).unwrap().to_string(), "1234.56");
+        assert_eq!(parse_amount_value("", "amount", 2).unwrap().to_string(), "0.00");
+        assert_eq!(parse_amount_value("12a", "zamount", 2), None);
+        assert_eq!(parse_amount_value("12", "string", 2), None);
+    }
+
+    #[test]
+    fn format_decimal_value_groups_thousands() {
+        assert_eq!(format_decimal_value(&Decimal::new(123456, 2), false), "1234,56");
+        assert_eq!(format_decimal_value(&Decimal::new(123456, 2), true), "1.234,56");
+        assert_eq!(format_decimal_value(&Decimal::new(-123456789, 2), true), "-1.234.567,89");
+    }
+
+    #[test]
+    fn parse_date_value_treats_zeros_as_missing() {
+        assert_eq!(parse_date_value("20231029", "%Y%m%d").unwrap(), NaiveDate::from_ymd_opt(2023, 10, 29));
+        assert_eq!(parse_date_value("00000000", "%Y%m%d").unwrap(), None);
+        assert_eq!(parse_date_value("  ", "%Y%m%d").unwrap(), None);
+        assert!(parse_date_value("29/10/2023", "%Y%m%d").is_err());
+    }
+
+    #[test]
+    fn format_date_value_falls_back_to_iso() {
+        let date = NaiveDate::from_ymd_opt(2023, 10, 29).unwrap();
+        assert_eq!(format_date_value(&date, ""), "29-10-2023");
+        assert_eq!(format_date_value(&date, "%d/%m/%Y"), "29/10/2023");
+        assert_eq!(format_date_value(&date, "%Q"), "2023-10-29");
+    }
+
+    #[test]
+    fn split_delimited_line_honours_quotes() {
+        assert_eq!(split_delimited_line("01;\"Peña; Juan\";0,00", ";"), ["01", "Peña; Juan", "0,00"]);
+        assert_eq!(split_delimited_line("01;\"Dijo \"\"hola\"\"\";", ";"), ["01", "Dijo \"hola\"", ""]);
+        assert_eq!(split_delimited_line(";;", ";"), ["", "", ""]);
+        assert_eq!(split_delimited_line("a||b|c", "||"), ["a", "b|c"]);
+        assert_eq!(split_delimited_line("a b\"c", ";"), ["a b\"c"]);
+    }
+
+    #[test]
+    fn delimited_formats_split_each_line_on_the_delimiter() {
+        let schema = schema_from(r#"formats:
+  padron:
+    category: Pruebas
+    delimiter: ;
+    encoding: utf-8
+    fields[3]{nombre,len,tipo,param1,param2}:
+      "CUIT",11,string,"",""
+      "Nombre",20,string,"",""
+      "Importe",12,amount,"2",""
+"#);
+        let values = values_of(&schema, "padron", "20123456789;\"Peña; Juan\";1.234,56\r\n30111111118; Otro ;\n");
+        assert_eq!(values[0], [
+            Value::Text("20123456789".to_string()),
+            Value::Text("Peña; Juan".to_string()),
+            Value::Decimal(Decimal::new(123456, 2)),
+        ]);
+        assert_eq!(values[1], [
+            Value::Text("30111111118".to_string()),
+            Value::Text("Otro".to_string()),
+            Value::Decimal(Decimal::new(0, 2)),
+        ]);
+    }
+
+    #[test]
+    fn delimited_lines_with_extra_values_keep_the_declared_fields() {
+        let schema = schema_from(r#"formats:
+  padron:
+    category: Pruebas
+    delimiter: ;
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "CUIT",11,string,"",""
+      "Nombre",20,string,"",""
+"#);
+        let values = values_of(&schema, "padron", "20123456789;Juan;sobrante\n");
+        assert_eq!(values[0], [Value::Text("20123456789".to_string()), Value::Text("Juan".to_string())]);
+
+        assert_eq!(field_count_warning(1, 2, 2), None);
+        assert_eq!(
+            field_count_warning(1, 2, 3).as_deref(),
+            Some("Línea 1: Se esperaban 2 campos y se encontraron 3; se descartan los sobrantes.")
+        );
+        assert_eq!(field_count_warning(4, 2, 1).as_deref(), Some("Línea 4: Se esperaban 2 campos y se encontraron 1."));
+    }
+
+    #[test]
+    fn date_fields_use_the_input_pattern_and_render_with_the_output_pattern() {
+        let schema = schema_from(r#"formats:
+  fechas:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Alta",8,date,"%Y%m%d",""
+      "Baja",10,date,"%d/%m/%Y","%Y-%m-%d"
+"#);
+        let fields = &schema.formats["fechas"].fields;
+        let date = |y, m, d| Value::Date(NaiveDate::from_ymd_opt(y, m, d));
+
+        let values = values_of(&schema, "fechas", "2023102931/12/2024\n0000000031-12-2024\n");
+        assert_eq!(values[0], [date(2023, 10, 29), date(2024, 12, 31)]);
+        assert_eq!(values[0][0].render(&fields[0], false, false), "29-10-2023");
+        assert_eq!(values[0][1].render(&fields[1], false, false), "2024-12-31");
+        assert_eq!(values[1], [Value::Date(None), Value::Text("31-12-2024".to_string())]);
+    }
+
+    #[test]
+    fn fixed_width_fields_are_sliced_on_bytes_before_decoding() {
+        let schema = schema_from(r#"formats:
+  nombres:
+    category: Pruebas
+    delimiter: ""
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Nombre",6,string,"",""
+      "Código",2,string,"",""
+  nombres-utf8:
+    category: Pruebas
+    delimiter: ""
+    encoding: utf-8
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Nombre",6,string,"",""
+      "Código",2,string,"",""
+"#);
+        let expected = [Value::Text("Peña".to_string()), Value::Text("01".to_string())];
+
+        assert_eq!(values_of(&schema, "nombres", b"Pe\xf1a  01\r\n")[0], expected);
+        assert_eq!(values_of(&schema, "nombres-utf8", "Peña 01\n")[0], expected);
+    }
+
+    const MULTI: &str = r#"formats:
+  lote:
+    category: Pruebas
+    delimiter: ""
+    records[2]:
+      - name: cabecera
+        prefix: H
+        offset: 0
+        fields[2]{nombre,len,tipo,param1,param2}:
+          "Tipo",1,string,"",""
+          "Fecha",8,date,"%Y%m%d",""
+      - name: detalle
+        prefix: D
+        offset: 0
+        length: 11
+        fields[2]{nombre,len,tipo,param1,param2}:
+          "Tipo",1,string,"",""
+          "Importe",10,zamount,"2",""
+"#;
+
+    #[test]
+    fn select_record_layout_matches_prefix_and_length() {
+        let schema = schema_from(MULTI);
+        let format_def = &schema.formats["lote"];
+
+        assert_eq!(select_record_layout(format_def, b"H20231029"), Some(0));
+        assert_eq!(select_record_layout(format_def, b"D0000012345\r"), Some(1));
+        assert_eq!(select_record_layout(format_def, b"D00000123"), None);
+        assert_eq!(select_record_layout(format_def, b"X20231029"), None);
+    }
+
+    #[test]
+    fn multi_record_files_use_the_layout_of_each_line() {
+        let schema = schema_from(MULTI);
+        let records: Vec<Record> = read_records_from(input_from("H20231029\nD0000012345\nX\nD0000000100\n"), &schema.formats["lote"], &schema)
+            .map(|record| record.unwrap())
+            .collect();
+
+        let types: Vec<(usize, usize)> = records.iter().map(|r| (r.record_type, r.line_number)).collect();
+        assert_eq!(types, [(0, 1), (1, 2), (1, 4)]);
+        assert_eq!(records[1].values[1], Value::Decimal(Decimal::new(12345, 2)));
+    }
+
+    /// Lector que entrega una línea por lectura y, al agotarlas, falla.
+    struct FailingReader {
+        lines: usize,
+    }
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.lines == 0 {
+                return Err(std::io::Error::other("disco desconectado"));
+            }
+            self.lines -= 1;
+            std::io::Write::write(&mut &mut buf[..], b"20231029Primera   \n")
+        }
+    }
+
+    #[test]
+    fn records_are_read_as_the_stream_is_consumed() {
+        let schema = schema_from(FIXED);
+        let lines = DETECTION_SAMPLE_LINES + 5;
+        let reader = std::io::BufReader::new(FailingReader { lines });
+        let input = DataInput::from_reader("prueba.txt", Box::new(reader)).unwrap();
+        let mut records = read_records_from(input, &schema.formats["importes"], &schema);
+
+        // Los registros anteriores al error se entregan antes de que falle la lectura
+        for _ in 0..lines {
+            assert_eq!(records.next().unwrap().unwrap().values[1], Value::Text("Primera".to_string()));
+        }
+        assert!(records.next().unwrap().unwrap_err().to_string().contains("disco desconectado"));
+    }
+
+    #[test]
+    fn long_format_emits_one_row_per_value() {
+        let schema = schema_from(FIXED);
+        let stream = parse_to_records(input_from("20231029Primera   \n"), &schema.formats["importes"], &schema, false, false, true);
+
+        assert_eq!(stream.record_types[0].headers, ["#", "Columna", "Valor"]);
+        let rows: Vec<Vec<Value>> = stream.map(|record| record.unwrap().values).collect();
+        assert_eq!(rows, [
+            ["1", "Fecha", "29-10-2023"].map(|v| Value::Text(v.to_string())),
+            ["1", "Detalle", "Primera"].map(|v| Value::Text(v.to_string())),
+        ]);
+    }
+
+    #[test]
+    fn score_format_counts_matched_and_valid_lines() {
+        let schema = schema_from(FIXED);
+        let scores = score_formats(&input_from("010000000000012345\n990000000000012345\n"), &schema);
+        let codigos = score(&scores, "codigos");
+
+        assert_eq!((codigos.sample_lines, codigos.matched_lines, codigos.valid_lines), (2, 2, 1));
+        assert_eq!((codigos.passed_checks, codigos.failed_checks), (3, 1));
+        assert!(score(&scores, "importes").rejection.is_none());
+        let best: Vec<&str> = best_candidates(&scores).iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(best, ["codigos"]);
+    }
+
+    /// Errores de validación de un archivo en memoria, como (línea, posición, campo, regla).
+    fn validation_errors(schema: &ConfigSchema, format: &str, content: &str) -> Vec<(usize, usize, String, &'static str)> {
+        let mut errors = Vec::new();
+        let count = validate_file_from(input_from(content), &schema.formats[format], schema, |e| {
+            errors.push((e.line, e.offset, e.field.clone(), e.rule));
+            Ok(())
+        }).unwrap();
+        assert_eq!(count, errors.len());
+        errors
+    }
+
+    #[test]
+    fn validate_file_reports_each_rule_with_its_position() {
+        let schema = schema_from(FIXED);
+        let errors = validation_errors(&schema, "codigos", "010000000000012345\n99000000000001234X\n0100000000000123\n");
+
+        assert_eq!(errors, [
+            (2, 1, "Código".to_string(), "tabla"),
+            (2, 3, "Importe".to_string(), "numerico"),
+            (3, 1, String::new(), "longitud"),
+        ]);
+    }
+
+    #[test]
+    fn validate_file_checks_field_count_and_width_in_delimited_formats() {
+        let schema = schema_from(r#"formats:
+  padron:
+    category: Pruebas
+    delimiter: ;
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "CUIT",11,string,"",""
+      "Alta",8,date,"%Y%m%d",""
+"#);
+        let errors = validation_errors(&schema, "padron", "20123456789;20231029\n201234567890;20231329\n20123456789\n");
+
+        assert_eq!(errors, [
+            (2, 1, "CUIT".to_string(), "ancho_maximo"),
+            (2, 2, "Alta".to_string(), "fecha"),
+            (3, 1, String::new(), "cantidad_de_campos"),
+        ]);
+    }
+
+    #[test]
+    fn validate_file_stops_when_the_report_fails() {
+        let schema = schema_from(FIXED);
+        let result = validate_file_from(input_from("99\n99\n"), &schema.formats["codigos"], &schema, |_| Err("sin espacio".into()));
+        assert_eq!(result.unwrap_err().to_string(), "sin espacio");
+    }
+
+    #[test]
+    fn concat_record_streams_adds_source_and_line_columns() {
+        let schema = schema_from(FIXED);
+        let format_def = &schema.formats["importes"];
+        let streams = vec![
+            ("enero.txt".to_string(), parse_to_records(input_from("20230101Enero     \n"), format_def, &schema, false, false, false)),
+            ("febrero.txt".to_string(), parse_to_records(input_from("20230201Febrero   \n20230228Febrero   \n"), format_def, &schema, false, false, false)),
+        ];
+        let stream = concat_record_streams(streams);
+
+        assert_eq!(stream.record_types[0].headers, [SOURCE_FILE_COLUMN, LINE_COLUMN, "Fecha", "Detalle"]);
+        let fields = stream.record_types[0].fields.clone();
+        let rows: Vec<Vec<String>> = stream.map(|record| {
+            record.unwrap().values.iter().zip(fields.iter()).take(3).map(|(v, f)| v.render(f, false, false)).collect()
+        }).collect();
+        assert_eq!(rows, [
+            ["enero.txt", "1", "01-01-2023"],
+            ["febrero.txt", "1", "01-02-2023"],
+            ["febrero.txt", "2", "28-02-2023"],
+        ]);
+    }
+
+    #[test]
+    fn ebcdic_files_split_lines_on_nl_and_decode_each_field() {
+        let schema = schema_from(r#"formats:
+  extracto:
+    category: Pruebas
+    delimiter: ""
+    encoding: cp037
+    fields[2]{nombre,len,tipo,param1,param2}:
+      "Nombre",4,string,"",""
+      "Importe",3,zamount,"2",""
+"#);
+        let content = [0xC8, 0x96, 0x93, 0x81, 0xF1, 0xF2, 0xF3, 0x15, 0xD7, 0x85, 0x49, 0x81, 0xF0, 0xF0, 0xF5, 0x15];
+        let values = values_of(&schema, "extracto", content);
+
+        assert_eq!(values, [
+            [Value::Text("Hola".to_string()), Value::Decimal(Decimal::new(123, 2))],
+            [Value::Text("Peña".to_string()), Value::Decimal(Decimal::new(5, 2))],
+        ]);
+    }
+
+    #[test]
+    fn deduce_format_measures_ebcdic_lines_in_its_encoding() {
+        let schema = schema_from(r#"formats:
+  extracto:
+    category: Pruebas
+    delimiter: ""
+    encoding: cp037
+    fields[1]{nombre,len,tipo,param1,param2}:
+      "Nombre",4,string,"",""
+"#);
+        let content = [0xC8, 0x96, 0x93, 0x81, 0xF1, 0xF2, 0x40, 0x40, 0x15, 0xC8, 0x96, 0x93, 0x81, 0xF1, 0xF2, 0x15];
+        let error = deduce_format_from(&input_from(content), &schema).unwrap_err().to_string();
+        assert!(error.contains("longitud de registro de 6 bytes"), "{}", error);
+    }
+}
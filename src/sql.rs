@@ -0,0 +1,435 @@
+//! Módulo SQL para parseit-rs.
+//! Define los dialectos soportados por la salida SQL (PostgreSQL, SQLite, SQL Server
+//! y MySQL) y cómo se escriben en cada uno los identificadores, los tipos de columna
+//! y los literales de los valores tipados.
+//!
+use std::collections::HashSet;
+use std::str::FromStr;
+use rusqlite::ToSql;
+use rusqlite::types::{Null, ToSqlOutput};
+use crate::config::FieldDefinition;
+use crate::parse::{RenderOptions, Value};
+
+/// Dialecto SQL del script generado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SqlDialect {
+    #[default]
+    Postgres,
+    Sqlite,
+    SqlServer,
+    MySql,
+}
+
+impl FromStr for SqlDialect {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "postgres" | "postgresql" | "pg" => Ok(SqlDialect::Postgres),
+            "sqlite" => Ok(SqlDialect::Sqlite),
+            "sqlserver" | "mssql" => Ok(SqlDialect::SqlServer),
+            "mysql" | "mariadb" => Ok(SqlDialect::MySql),
+            _ => Err(format!(
+                "Dialecto SQL desconocido: '{}' (use postgres, sqlite, sqlserver o mysql)", name
+            )),
+        }
+    }
+}
+
+/// Opciones de la salida SQL.
+/// - dialect: Dialecto del script.
+/// - table_name: Nombre de la tabla (en los formatos multi-registro, prefijo de cada tabla).
+///   Puede incluir el esquema (ej: "staging.retenciones").
+/// - batch_size: Cantidad de filas por sentencia INSERT (1 = una sentencia por fila).
+/// - copy: Cargar los datos con bloques `COPY ... FROM stdin` (sólo PostgreSQL).
+/// - drop_table: Eliminar la tabla antes de crearla (`DROP TABLE IF EXISTS`).
+/// - database: Archivo de la base SQLite de la salida `sqlite`.
+/// - append: Agregar los registros a la tabla existente, si es compatible (salida `sqlite`).
+#[derive(Debug, Clone)]
+pub struct SqlOptions {
+    pub dialect: SqlDialect,
+    pub table_name: String,
+    pub batch_size: usize,
+    pub copy: bool,
+    pub drop_table: bool,
+    pub database: Option<String>,
+    pub append: bool,
+}
+
+/// Valor tipado ya resuelto para la salida SQL.
+pub enum SqlScalar {
+    Null,
+    Number(String),
+    Date(String),
+    Text(String),
+}
+
+impl ToSql for SqlScalar {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            SqlScalar::Null => Null.to_sql(),
+            // Los montos se envían como texto: la afinidad NUMERIC de la columna los convierte sin pérdida
+            SqlScalar::Number(text) | SqlScalar::Date(text) | SqlScalar::Text(text) => text.to_sql(),
+        }
+    }
+}
+
+/// Resuelve un valor tipado para la salida SQL: montos con punto decimal, fechas en
+/// formato ISO y `Null` para los valores vacíos. Un monto o una fecha inválidos no
+/// pueden cargarse en una columna numérica o de fecha, por lo que se informan y se
+/// resuelven como `Null`.
+pub fn sql_scalar(value: &Value, field: &FieldDefinition, options: RenderOptions) -> SqlScalar {
+    match value {
+        Value::Decimal(number) => SqlScalar::Number(number.to_string()),
+        Value::Date(Some(date)) => SqlScalar::Date(date.format("%Y-%m-%d").to_string()),
+        Value::Date(None) => SqlScalar::Null,
+        Value::Text(text) if matches!(field.tipo.as_str(), "zamount" | "amount" | "date") => {
+            if !text.is_empty() {
+                eprintln!(
+                    "Advertencia: Campo '{}' con valor inválido '{}'; se inserta NULL.",
+                    field.nombre, text
+                );
+            }
+            SqlScalar::Null
+        }
+        _ => {
+            let text = value.render(field, false, options.dont_use_tables);
+            if text.is_empty() { SqlScalar::Null } else { SqlScalar::Text(text) }
+        }
+    }
+}
+
+/// Cantidad de decimales de un campo de monto, con el mismo criterio que el parseo
+/// (`param1`, 2 por defecto; los `amount` sin decimales usan 2).
+pub(crate) fn amount_decimal_places(field: &FieldDefinition) -> u32 {
+    match field.param1.parse::<u32>() {
+        Ok(0) if field.tipo == "amount" => 2,
+        Ok(places) => places,
+        Err(_) => 2,
+    }
+}
+
+/// Convierte un nombre (ej: "Código de Jurisdicción") en un identificador SQL simple:
+/// minúsculas sin acentos, con `_` en lugar de espacios y símbolos.
+///
+/// ## Argumentos
+/// - `name`: Nombre original.
+/// - `max_len`: Longitud máxima del identificador en el dialecto.
+///
+/// ## Retorno
+/// String - Identificador saneado (ej: "codigo_de_jurisdiccion"). Si comienza con un
+/// dígito se le antepone `_`; si queda vacío se devuelve una cadena vacía.
+///
+/// ## Ejemplo
+/// ```
+/// # use parseit::sql::sanitize_identifier;
+/// assert_eq!(sanitize_identifier("Grupo Percep.", 63), "grupo_percep");
+/// ```
+pub fn sanitize_identifier(name: &str, max_len: usize) -> String {
+    let mut identifier = String::new();
+
+    for c in name.to_lowercase().chars() {
+        let c = match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ñ' => 'n',
+            'ç' => 'c',
+            c if c.is_ascii_alphanumeric() => c,
+            _ => '_',
+        };
+        // Un solo '_' entre palabras, y ninguno al principio
+        if c == '_' && (identifier.is_empty() || identifier.ends_with('_')) {
+            continue;
+        }
+        identifier.push(c);
+    }
+
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier.truncate(max_len);
+
+    identifier.trim_end_matches('_').to_string()
+}
+
+impl SqlDialect {
+    /// Longitud máxima de un identificador en el dialecto.
+    pub fn max_identifier_len(&self) -> usize {
+        match self {
+            SqlDialect::Postgres => 63,
+            SqlDialect::MySql => 64,
+            SqlDialect::SqlServer => 128,
+            SqlDialect::Sqlite => 128,
+        }
+    }
+
+    /// Encierra un identificador con las comillas del dialecto: `"x"` en PostgreSQL y
+    /// SQLite, `[x]` en SQL Server y `` `x` `` en MySQL.
+    pub fn quote_identifier(&self, identifier: &str) -> String {
+        match self {
+            SqlDialect::Postgres | SqlDialect::Sqlite => format!("\"{}\"", identifier.replace('"', "\"\"")),
+            SqlDialect::SqlServer => format!("[{}]", identifier.replace(']', "]]")),
+            SqlDialect::MySql => format!("`{}`", identifier.replace('`', "``")),
+        }
+    }
+
+    /// Sanea un nombre de tabla. Cada parte separada por `.` (esquema y tabla) se
+    /// sanea por separado.
+    ///
+    /// ## Retorno
+    /// `Vec<String>` - Partes del nombre sin comillas (ej: `["staging", "padron_iibb"]`).
+    pub fn table_name_parts(&self, name: &str) -> Vec<String> {
+        name.split('.')
+            .map(|part| {
+                let identifier = sanitize_identifier(part, self.max_identifier_len());
+                if identifier.is_empty() { "processed_data".to_string() } else { identifier }
+            })
+            .collect()
+    }
+
+    /// Sanea y encierra un nombre de tabla (ver `table_name_parts`).
+    ///
+    /// ## Ejemplo
+    /// ```
+    /// # use parseit::sql::SqlDialect;
+    /// assert_eq!(SqlDialect::Postgres.table_identifier("staging.Padron-iibb"), "\"staging\".\"padron_iibb\"");
+    /// ```
+    pub fn table_identifier(&self, name: &str) -> String {
+        self.table_name_parts(name).iter()
+            .map(|part| self.quote_identifier(part))
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    /// Sanea los nombres de columna de una tabla, sin repetidos: los nombres que
+    /// coinciden después de sanearse reciben un sufijo numérico (`_2`, `_3`...) y los
+    /// que quedan vacíos se llaman `col_<n>`.
+    ///
+    /// ## Argumentos
+    /// - `headers`: Encabezados de las columnas.
+    ///
+    /// ## Retorno
+    /// `Vec<String>` - Nombres de columna sin comillas, en el mismo orden.
+    pub fn column_names(&self, headers: &[String]) -> Vec<String> {
+        let max_len = self.max_identifier_len();
+        let mut used = HashSet::new();
+
+        headers.iter().enumerate()
+            .map(|(index, header)| {
+                let mut base = sanitize_identifier(header, max_len);
+                if base.is_empty() {
+                    base = format!("col_{}", index + 1);
+                }
+
+                let mut identifier = base.clone();
+                let mut suffix = 2;
+                while !used.insert(identifier.clone()) {
+                    let suffix_text = format!("_{}", suffix);
+                    let mut truncated = base.clone();
+                    truncated.truncate(max_len - suffix_text.len());
+                    identifier = format!("{}{}", truncated, suffix_text);
+                    suffix += 1;
+                }
+
+                identifier
+            })
+            .collect()
+    }
+
+    /// Sanea y encierra los nombres de columna de una tabla (ver `column_names`).
+    ///
+    /// ## Argumentos
+    /// - `headers`: Encabezados de las columnas.
+    ///
+    /// ## Retorno
+    /// `Vec<String>` - Identificadores listos para el script, en el mismo orden.
+    pub fn column_identifiers(&self, headers: &[String]) -> Vec<String> {
+        self.column_names(headers).iter().map(|name| self.quote_identifier(name)).collect()
+    }
+
+    /// Tipo SQL de una columna según la definición de su campo.
+    ///
+    /// ## Argumentos
+    /// - `field`: Definición del campo.
+    /// - `width`: Ancho estimado de la columna ya formateada (para los códigos con descripción).
+    ///
+    /// ## Retorno
+    /// String - `DECIMAL(len, decimales)` para los montos, `DATE` para las fechas y
+    /// `VARCHAR(len)` para el resto (`NVARCHAR` en SQL Server).
+    pub fn column_type(&self, field: &FieldDefinition, width: usize) -> String {
+        let varchar = if *self == SqlDialect::SqlServer { "NVARCHAR" } else { "VARCHAR" };
+
+        match field.tipo.as_str() {
+            "zamount" | "amount" => {
+                let scale = amount_decimal_places(field);
+                format!("DECIMAL({}, {})", field.len.max(scale as usize + 1), scale)
+            }
+            "date" => "DATE".to_string(),
+            "table" => format!("{}({})", varchar, width.max(1)),
+            _ => format!("{}({})", varchar, field.len.max(1)),
+        }
+    }
+
+    /// Convierte un valor tipado en un literal SQL del dialecto: montos con punto
+    /// decimal, fechas en formato ISO entre comillas, textos entre comillas simples y
+    /// `NULL` para los valores vacíos.
+    ///
+    /// Las comillas simples se escapan como `''`; en MySQL también se escapa `\`, y en
+    /// SQL Server los textos se escriben como `N'...'` para conservar los acentos.
+    ///
+    /// ## Argumentos
+    /// - `value`: Valor a convertir.
+    /// - `field`: Definición del campo de la columna.
+    /// - `options`: Opciones de formateo (para los códigos de las tablas de lookup).
+    ///
+    /// ## Retorno
+    /// String - Literal listo para la sentencia INSERT.
+    pub fn literal(&self, value: &Value, field: &FieldDefinition, options: RenderOptions) -> String {
+        match sql_scalar(value, field, options) {
+            SqlScalar::Null => "NULL".to_string(),
+            SqlScalar::Number(number) => number,
+            SqlScalar::Date(date) => format!("'{}'", date),
+            SqlScalar::Text(text) => {
+                let escaped = match self {
+                    SqlDialect::MySql => text.replace('\\', "\\\\").replace('\'', "''"),
+                    _ => text.replace('\'', "''"),
+                };
+                match self {
+                    SqlDialect::SqlServer => format!("N'{}'", escaped),
+                    _ => format!("'{}'", escaped),
+                }
+            }
+        }
+    }
+}
+
+/// Convierte un valor tipado en un valor de un bloque `COPY ... FROM stdin` de
+/// PostgreSQL (formato de texto): `\N` para `NULL` y las barras, tabulaciones y
+/// saltos de línea escapados con `\`.
+///
+/// ## Argumentos
+/// - `value`: Valor a convertir.
+/// - `field`: Definición del campo de la columna.
+/// - `options`: Opciones de formateo (para los códigos de las tablas de lookup).
+///
+/// ## Retorno
+/// String - Valor listo para separarse con tabulaciones.
+pub fn copy_value(value: &Value, field: &FieldDefinition, options: RenderOptions) -> String {
+    match sql_scalar(value, field, options) {
+        SqlScalar::Null => "\\N".to_string(),
+        SqlScalar::Number(text) | SqlScalar::Date(text) => text,
+        SqlScalar::Text(text) => text
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn field(tipo: &str, len: usize, param1: &str) -> FieldDefinition {
+        FieldDefinition {
+            nombre: "Campo".to_string(),
+            len,
+            tipo: tipo.to_string(),
+            param1: param1.to_string(),
+            param2: String::new(),
+        }
+    }
+
+    #[test]
+    fn column_type_follows_the_field_type() {
+        let dialect = SqlDialect::Postgres;
+        assert_eq!(dialect.column_type(&field("zamount", 15, "2"), 0), "DECIMAL(15, 2)");
+        assert_eq!(dialect.column_type(&field("amount", 1, "0"), 0), "DECIMAL(3, 2)");
+        assert_eq!(dialect.column_type(&field("date", 8, "%Y%m%d"), 0), "DATE");
+        assert_eq!(dialect.column_type(&field("table", 2, "provincias"), 17), "VARCHAR(17)");
+        assert_eq!(SqlDialect::SqlServer.column_type(&field("string", 30, ""), 0), "NVARCHAR(30)");
+    }
+
+    #[test]
+    fn literal_writes_typed_values() {
+        let options = RenderOptions::default();
+        let dialect = SqlDialect::Postgres;
+        let date = NaiveDate::from_ymd_opt(2023, 10, 29);
+
+        assert_eq!(dialect.literal(&Value::Decimal(Decimal::new(-123456, 2)), &field("amount", 12, "2"), options), "-1234.56");
+        assert_eq!(dialect.literal(&Value::Date(date), &field("date", 8, "%Y%m%d"), options), "'2023-10-29'");
+        assert_eq!(dialect.literal(&Value::Date(None), &field("date", 8, "%Y%m%d"), options), "NULL");
+        assert_eq!(dialect.literal(&Value::Text("12a".to_string()), &field("zamount", 8, "2"), options), "NULL");
+        assert_eq!(dialect.literal(&Value::Text(String::new()), &field("string", 8, ""), options), "NULL");
+        assert_eq!(dialect.literal(&Value::Text("D'Amico".to_string()), &field("string", 8, ""), options), "'D''Amico'");
+    }
+
+    #[test]
+    fn literal_renders_codes_with_their_description_unless_disabled() {
+        let value = Value::Coded { code: "1".to_string(), description: Some("Retención".to_string()) };
+        let tipo = field("table", 1, "tipo_operacion");
+        let codes_only = RenderOptions { dont_use_tables: true, ..RenderOptions::default() };
+
+        assert_eq!(SqlDialect::Postgres.literal(&value, &tipo, RenderOptions::default()), "'1 - Retención'");
+        assert_eq!(SqlDialect::Postgres.literal(&value, &tipo, codes_only), "'1'");
+    }
+
+    #[test]
+    fn sanitize_identifier_removes_accents_and_symbols() {
+        assert_eq!(sanitize_identifier("Código de Jurisdicción", 63), "codigo_de_jurisdiccion");
+        assert_eq!(sanitize_identifier("  Nº  Comprobante (AFIP) ", 63), "n_comprobante_afip");
+        assert_eq!(sanitize_identifier("1er Vencimiento", 63), "_1er_vencimiento");
+        assert_eq!(sanitize_identifier("Año", 63), "ano");
+        assert_eq!(sanitize_identifier("$$$", 63), "");
+    }
+
+    #[test]
+    fn sanitize_identifier_truncates_without_trailing_underscores() {
+        assert_eq!(sanitize_identifier("Monto total", 6), "monto");
+        assert_eq!(sanitize_identifier("Monto total", 8), "monto_to");
+    }
+
+    #[test]
+    fn column_names_are_unique_and_never_empty() {
+        let headers: Vec<String> = ["Código", "codigo", "CÓDIGO", "¿?"].iter().map(|h| h.to_string()).collect();
+        assert_eq!(SqlDialect::Postgres.column_names(&headers), ["codigo", "codigo_2", "codigo_3", "col_4"]);
+
+        let long: Vec<String> = vec!["a".repeat(70), "a".repeat(70)];
+        let names = SqlDialect::Postgres.column_names(&long);
+        assert_eq!(names[1], format!("{}_2", "a".repeat(61)));
+        assert!(names.iter().all(|name| name.len() <= 63));
+    }
+
+    #[test]
+    fn identifiers_are_quoted_per_dialect() {
+        assert_eq!(SqlDialect::Postgres.quote_identifier("a\"b"), "\"a\"\"b\"");
+        assert_eq!(SqlDialect::SqlServer.table_identifier("dbo.Padron"), "[dbo].[padron]");
+        assert_eq!(SqlDialect::MySql.table_identifier("Padrón IIBB"), "`padron_iibb`");
+        assert_eq!(SqlDialect::Sqlite.table_identifier("$$"), "\"processed_data\"");
+    }
+
+    #[test]
+    fn text_literals_are_escaped_per_dialect() {
+        let text = Value::Text("C:\\D'Amico".to_string());
+        let nombre = field("string", 20, "");
+        let options = RenderOptions::default();
+
+        assert_eq!(SqlDialect::MySql.literal(&text, &nombre, options), "'C:\\\\D''Amico'");
+        assert_eq!(SqlDialect::SqlServer.literal(&text, &nombre, options), "N'C:\\D''Amico'");
+        assert_eq!(copy_value(&Value::Text("a\tb\\c".to_string()), &nombre, options), "a\\tb\\\\c");
+        assert_eq!(copy_value(&Value::Date(None), &field("date", 8, ""), options), "\\N");
+    }
+
+    #[test]
+    fn dialects_are_parsed_by_name_and_alias() {
+        assert_eq!("PostgreSQL".parse::<SqlDialect>(), Ok(SqlDialect::Postgres));
+        assert_eq!("mssql".parse::<SqlDialect>(), Ok(SqlDialect::SqlServer));
+        assert_eq!("mariadb".parse::<SqlDialect>(), Ok(SqlDialect::MySql));
+        assert!("oracle".parse::<SqlDialect>().is_err());
+    }
+}